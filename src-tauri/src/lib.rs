@@ -1,75 +1,309 @@
 mod modules;
-use log::{debug, info};
+use log::{debug, error, info, warn};
+use modules::backup;
+use modules::bulk;
 use modules::clipboard;
 use modules::db;
+use modules::encryption;
+use modules::error::AppError;
+use modules::export;
 use modules::hotkey;
+use modules::import;
+use modules::location;
+use modules::maintenance;
+use modules::ordering;
+use modules::retention;
+use modules::revisions;
+use modules::search;
+use modules::settings;
+use modules::tags;
+use modules::trash;
 use modules::window_manager;
 use tauri::menu::{Menu, MenuItem};
 use tauri::tray::{TrayIconBuilder, TrayIconEvent};
 use tauri::AppHandle;
 #[tauri::command]
-fn get_clipboard_history() -> Result<Vec<db::PasteItem>, String> {
-    db::get_all_contents().map_err(|e| e.to_string())
+fn get_clipboard_history() -> Result<Vec<db::PasteItem>, AppError> {
+    db::get_all_contents()
+}
+#[tauri::command]
+fn get_history_page(
+    directory_id: Option<i64>,
+    tags: Option<Vec<String>>,
+    sort: Option<db::HistorySort>,
+    limit: Option<i64>,
+    cursor: Option<db::HistoryCursor>,
+) -> Result<db::HistoryPage, AppError> {
+    db::get_contents_page(
+        directory_id,
+        &tags.unwrap_or_default(),
+        sort,
+        limit,
+        cursor.as_ref(),
+    )
+}
+#[tauri::command]
+fn get_directory_history(
+    directory_id: i64,
+    sort: Option<db::HistorySort>,
+) -> Result<Vec<db::PasteItem>, AppError> {
+    db::get_directory_contents(directory_id, sort)
+}
+#[tauri::command]
+fn search_items(
+    query: String,
+    directory_id: Option<i64>,
+    sort: Option<search::SearchSort>,
+    limit: Option<i64>,
+) -> Result<Vec<search::SearchResult>, AppError> {
+    search::search_items(&query, directory_id, sort.unwrap_or_default(), limit)
 }
 #[tauri::command]
 fn create_history_item(
     content: String,
-    directory: String,
+    directory_id: i64,
     memo: Option<String>,
-) -> Result<i64, String> {
-    db::post_content(&content, &directory, memo.as_deref()).map_err(|e| e.to_string())
+) -> Result<i64, AppError> {
+    db::post_content(&content, directory_id, memo.as_deref())
 }
 #[tauri::command]
-fn paste_text(text: String) -> Result<(), String> {
+fn paste_text(text: String) -> Result<(), AppError> {
     clipboard::paste_text(text)
 }
 #[tauri::command]
+fn paste_item(id: i64) -> Result<(), AppError> {
+    let item = db::get_item(id)?;
+    db::record_use(id)?;
+    clipboard::paste_text(item.content)
+}
+#[tauri::command]
 fn toggle_main_window(app: AppHandle) {
     hotkey::toggle_main_window(&app);
 }
 #[tauri::command]
-fn get_directories() -> Result<Vec<db::DirectoryInfo>, String> {
-    db::get_directories().map_err(|e| e.to_string())
+fn get_directories() -> Result<Vec<db::DirectoryInfo>, AppError> {
+    db::get_directories()
+}
+#[tauri::command]
+fn create_directory(name: String, parent_id: Option<i64>) -> Result<i64, AppError> {
+    db::create_directory(&name, parent_id)
+}
+#[tauri::command]
+fn move_directory(id: i64, parent_id: Option<i64>) -> Result<(), AppError> {
+    db::move_directory(id, parent_id)
 }
 #[tauri::command]
-fn create_directory(name: String) -> Result<i64, String> {
-    db::create_directory(&name).map_err(|e| e.to_string())
+fn set_directory_unique_content(directory_id: i64, enabled: bool) -> Result<(), AppError> {
+    db::set_unique_content(directory_id, enabled)
 }
 #[tauri::command]
-fn rename_directory(old_name: String, new_name: String) -> Result<(), String> {
-    db::rename_directory(&old_name, &new_name).map_err(|e| e.to_string())
+fn get_retention_policy(directory_id: i64) -> Result<retention::RetentionPolicy, AppError> {
+    retention::get_policy(directory_id)
 }
 #[tauri::command]
-fn delete_directory(name: String) -> Result<(), String> {
-    db::delete_directory(&name).map_err(|e| e.to_string())
+fn set_retention_policy(
+    directory_id: i64,
+    policy: retention::RetentionPolicy,
+) -> Result<usize, AppError> {
+    retention::set_policy(directory_id, policy)
+}
+#[tauri::command]
+fn apply_retention_policies() -> Result<usize, AppError> {
+    retention::enforce_all()
+}
+#[tauri::command]
+fn reorder_directory(id: i64, placement: ordering::Placement<i64>) -> Result<(), AppError> {
+    ordering::reorder_directory(id, placement)
+}
+#[tauri::command]
+fn reorder_history_item(id: i64, placement: ordering::Placement<i64>) -> Result<(), AppError> {
+    ordering::reorder_item(id, placement)
+}
+#[tauri::command]
+fn rename_directory(id: i64, new_name: String) -> Result<(), AppError> {
+    db::rename_directory(id, &new_name)
+}
+#[tauri::command]
+fn delete_directory(id: i64, children: Option<db::ChildPolicy>) -> Result<(), AppError> {
+    db::delete_directory(id, children.unwrap_or_default())
 }
 #[tauri::command]
 fn update_history_item(
     id: i64,
     content: String,
-    directory: String,
+    directory_id: i64,
     memo: Option<String>,
-) -> Result<(), String> {
-    db::update_content(id, &content, &directory, memo.as_deref())
-        .map(|_| ())
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    db::update_content(id, &content, directory_id, memo.as_deref()).map(|_| ())
 }
 #[tauri::command]
-fn delete_history_item(id: i64) -> Result<(), String> {
-    db::delete_history_item(id).map_err(|e| e.to_string())
+fn delete_history_item(id: i64) -> Result<(), AppError> {
+    db::delete_history_item(id)
 }
 #[tauri::command]
-fn get_setting(key: String) -> Result<Option<String>, String> {
-    db::get_setting(&key).map_err(|e| e.to_string())
+fn move_history_items(
+    ids: Vec<i64>,
+    directory_id: i64,
+) -> Result<Vec<bulk::ItemOutcome>, AppError> {
+    bulk::move_items(&ids, directory_id)
 }
 #[tauri::command]
-fn update_setting(key: String, value: String) -> Result<(), String> {
-    db::set_setting(&key, &value).map_err(|e| e.to_string())?;
-    if key == "mouse_edge_enabled" {
-        window_manager::update_mouse_edge_enabled(value == "true");
-    }
+fn copy_history_items(
+    ids: Vec<i64>,
+    directory_id: i64,
+) -> Result<Vec<bulk::ItemOutcome>, AppError> {
+    bulk::copy_items(&ids, directory_id)
+}
+#[tauri::command]
+fn delete_history_items(ids: Vec<i64>) -> Result<Vec<bulk::ItemOutcome>, AppError> {
+    bulk::delete_items(&ids)
+}
+#[tauri::command]
+fn pin_history_item(id: i64) -> Result<(), AppError> {
+    db::set_pinned(id, true)
+}
+#[tauri::command]
+fn unpin_history_item(id: i64) -> Result<(), AppError> {
+    db::set_pinned(id, false)
+}
+#[tauri::command]
+fn get_item_revisions(id: i64) -> Result<Vec<revisions::ItemRevision>, AppError> {
+    revisions::get_item_revisions(id)
+}
+#[tauri::command]
+fn restore_item_revision(revision_id: i64) -> Result<i64, AppError> {
+    revisions::restore_item_revision(revision_id)
+}
+#[tauri::command]
+fn export_directories(
+    path: String,
+    format: export::ExportFormat,
+    directories: Option<Vec<i64>>,
+) -> Result<export::ExportSummary, AppError> {
+    export::export_directories(&path, format, directories.as_deref())
+}
+#[tauri::command]
+fn import_file(
+    path: String,
+    format: import::ImportFormat,
+    directory: Option<String>,
+) -> Result<import::ImportSummary, AppError> {
+    import::import_file(&path, format, directory.as_deref())
+}
+#[tauri::command]
+fn create_backup() -> Result<backup::BackupInfo, AppError> {
+    backup::create_backup()
+        .and_then(|info| backup::rotate_backups(backup::retention_count()).map(|_| info))
+}
+#[tauri::command]
+fn list_backups() -> Result<Vec<backup::BackupInfo>, AppError> {
+    backup::list_backups()
+}
+#[tauri::command]
+fn restore_backup(name: String) -> Result<(), AppError> {
+    backup::restore_backup(&name)?;
+    settings::reload();
     Ok(())
 }
+#[tauri::command]
+fn get_encryption_status() -> encryption::EncryptionStatus {
+    encryption::status()
+}
+#[tauri::command]
+fn unlock_database(passphrase: String) -> Result<Option<maintenance::RecoveryReport>, AppError> {
+    let report = encryption::unlock(&passphrase)?;
+    settings::reload();
+    Ok(report)
+}
+#[tauri::command]
+fn enable_encryption(passphrase: String) -> Result<encryption::RekeyReport, AppError> {
+    encryption::enable(&passphrase)
+}
+#[tauri::command]
+fn change_encryption_passphrase(
+    current: String,
+    new: String,
+) -> Result<encryption::RekeyReport, AppError> {
+    encryption::change_passphrase(&current, &new)
+}
+#[tauri::command]
+fn disable_encryption(current: String) -> Result<encryption::RekeyReport, AppError> {
+    encryption::disable(&current)
+}
+#[tauri::command]
+fn get_data_location() -> location::DataLocation {
+    location::current()
+}
+#[tauri::command]
+fn move_database(destination: String) -> Result<location::DataLocation, AppError> {
+    location::move_database(&destination)
+}
+#[tauri::command]
+fn check_database(full: Option<bool>) -> Result<Vec<String>, AppError> {
+    if full.unwrap_or(false) {
+        maintenance::integrity_check()
+    } else {
+        maintenance::quick_check()
+    }
+}
+#[tauri::command]
+fn vacuum_database() -> Result<u64, AppError> {
+    maintenance::vacuum()
+}
+#[tauri::command]
+fn analyze_database() -> Result<(), AppError> {
+    maintenance::analyze()
+}
+#[tauri::command]
+fn recover_database() -> Result<maintenance::RecoveryReport, AppError> {
+    let report = maintenance::recover()?;
+    settings::reload();
+    Ok(report)
+}
+#[tauri::command]
+fn get_diagnostics() -> Result<maintenance::DiagnosticReport, AppError> {
+    maintenance::diagnostics()
+}
+#[tauri::command]
+fn get_tags() -> Result<Vec<tags::TagInfo>, AppError> {
+    tags::get_tags()
+}
+#[tauri::command]
+fn add_item_tag(id: i64, tag: String) -> Result<(), AppError> {
+    tags::add_item_tag(id, &tag)
+}
+#[tauri::command]
+fn remove_item_tag(id: i64, tag: String) -> Result<(), AppError> {
+    tags::remove_item_tag(id, &tag)
+}
+#[tauri::command]
+fn list_trash() -> Result<trash::TrashContents, AppError> {
+    trash::list_trash()
+}
+#[tauri::command]
+fn restore_history_item(id: i64) -> Result<(), AppError> {
+    trash::restore_item(id)
+}
+#[tauri::command]
+fn restore_directory(id: i64) -> Result<(), AppError> {
+    trash::restore_directory(id)
+}
+#[tauri::command]
+fn empty_trash() -> Result<(), AppError> {
+    trash::empty_trash()
+}
+#[tauri::command]
+fn get_setting(key: String) -> Result<Option<String>, AppError> {
+    settings::get(&key).map(Some)
+}
+#[tauri::command]
+fn get_settings() -> Vec<settings::SettingInfo> {
+    settings::all()
+}
+#[tauri::command]
+fn update_setting(key: String, value: String) -> Result<(), AppError> {
+    settings::update(&key, &value)
+}
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -97,11 +331,31 @@ pub fn run() {
             hotkey::save_current_app();
             #[cfg(target_os = "macos")]
             app.set_activation_policy(tauri::ActivationPolicy::Accessory);
-            let _conn = db::init_db().expect("Failed to initialize database");
-            info!("Database initialized");
-            if let Ok(Some(val)) = db::get_setting("mouse_edge_enabled") {
-                window_manager::update_mouse_edge_enabled(val == "true");
+            if encryption::is_locked() {
+                info!("Database is encrypted, waiting for passphrase");
+            } else {
+                match maintenance::startup_check() {
+                    Ok(None) => info!("Database initialized"),
+                    Ok(Some(report)) => {
+                        warn!(
+                            "Database recovered, damaged copy kept at {}",
+                            report.moved_to
+                        )
+                    }
+                    Err(e) => error!("Failed to initialize database: {:?}", e),
+                }
             }
+            trash::start_purge_scheduler();
+            retention::start_retention_sweeper();
+            backup::start_backup_scheduler();
+            window_manager::subscribe_settings();
+            let settings_handle = app.handle().clone();
+            settings::subscribe_all(move |change| {
+                use tauri::Emitter;
+                if let Err(e) = settings_handle.emit(settings::CHANGE_EVENT, change) {
+                    error!("Failed to emit setting change: {:?}", e);
+                }
+            });
             let db_path = db::get_path();
             debug!("Database path: {:?}", db_path);
             let quit_i = MenuItem::with_id(app, "quit", "Quit PasteSheet", true, None::<&str>)?;
@@ -193,16 +447,59 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             get_clipboard_history,
+            get_history_page,
+            get_directory_history,
+            search_items,
             get_directories,
             create_directory,
+            move_directory,
+            set_directory_unique_content,
+            get_retention_policy,
+            set_retention_policy,
+            apply_retention_policies,
+            reorder_directory,
+            reorder_history_item,
             rename_directory,
             delete_directory,
             paste_text,
+            paste_item,
             toggle_main_window,
             update_history_item,
             delete_history_item,
+            move_history_items,
+            copy_history_items,
+            delete_history_items,
             create_history_item,
+            pin_history_item,
+            unpin_history_item,
+            get_item_revisions,
+            restore_item_revision,
+            get_tags,
+            export_directories,
+            import_file,
+            create_backup,
+            list_backups,
+            restore_backup,
+            get_encryption_status,
+            unlock_database,
+            enable_encryption,
+            change_encryption_passphrase,
+            disable_encryption,
+            get_data_location,
+            move_database,
+            check_database,
+            vacuum_database,
+            analyze_database,
+            recover_database,
+            get_diagnostics,
+            add_item_tag,
+            remove_item_tag,
+            list_trash,
+            restore_history_item,
+            restore_directory,
+            empty_trash,
             get_setting,
+            get_settings,
             update_setting
         ])
         .run(tauri::generate_context!())
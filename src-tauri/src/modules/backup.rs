@@ -0,0 +1,188 @@
+use crate::modules::error::{AppError, AppResult};
+use crate::modules::{db, migrations, settings};
+use log::{error, info};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+const BACKUP_DIRECTORY: &str = "paste_sheets_backups";
+const BACKUP_PREFIX: &str = "paste_sheets-";
+const BACKUP_EXTENSION: &str = ".db";
+const PAGES_PER_STEP: i32 = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct BackupInfo {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub created_at: String,
+}
+pub fn backup_dir() -> PathBuf {
+    let db_path = PathBuf::from(db::get_path());
+    db_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+        .join(BACKUP_DIRECTORY)
+}
+// Names look like paste_sheets-20260101-093000123.db so they sort chronologically.
+fn created_at_from_name(name: &str) -> Option<String> {
+    let stamp = name
+        .strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_EXTENSION)?;
+    let digits: String = stamp.chars().filter(char::is_ascii_digit).collect();
+    if digits.len() < 14 {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{} {}:{}:{}",
+        &digits[0..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14]
+    ))
+}
+pub fn list_backups() -> AppResult<Vec<BackupInfo>> {
+    let dir = backup_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(created_at) = created_at_from_name(&name) else {
+            continue;
+        };
+        backups.push(BackupInfo {
+            path: entry.path().to_string_lossy().to_string(),
+            size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+            name,
+            created_at,
+        });
+    }
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+pub fn copy_database(src: &Connection, dst: &mut Connection) -> Result<()> {
+    Backup::new(src, dst)?.run_to_completion(PAGES_PER_STEP, Duration::ZERO, None)
+}
+// Pages come from a separate read-only connection with a pause between steps, so the app
+// keeps using the database while a backup runs. The open read transaction pins a single
+// snapshot; otherwise every write in between would restart the copy.
+pub fn create_backup() -> AppResult<BackupInfo> {
+    let dir = backup_dir();
+    fs::create_dir_all(&dir)?;
+    let src = Connection::open_with_flags(db::get_path(), OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    db::apply_key(&src)?;
+    let stamp: String = src.query_row("SELECT strftime('%Y%m%d-%H%M%f', 'now')", [], |row| {
+        row.get(0)
+    })?;
+    src.execute_batch("BEGIN; SELECT COUNT(*) FROM sqlite_master;")?;
+    let backup = format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        stamp.replace('.', ""),
+        BACKUP_EXTENSION
+    );
+    let path = dir.join(&backup);
+    let partial = dir.join(format!("{}.partial", backup));
+    {
+        let mut dst = Connection::open(&partial)?;
+        db::apply_key(&dst)?;
+        Backup::new(&src, &mut dst)?.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+        dst.pragma_update_and_check(None, "journal_mode", "DELETE", |row| {
+            row.get::<_, String>(0)
+        })?;
+    }
+    src.execute_batch("COMMIT")?;
+    fs::rename(&partial, &path)?;
+    list_backups()?
+        .into_iter()
+        .find(|info| info.name == backup)
+        .ok_or_else(|| AppError::not_found(&format!("Backup '{}'", backup)))
+}
+pub fn rotate_backups(retention: usize) -> AppResult<usize> {
+    let mut removed = 0;
+    for backup in list_backups()?.into_iter().skip(retention.max(1)) {
+        fs::remove_file(&backup.path)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+pub fn verify_backup(path: &Path) -> AppResult<()> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    db::apply_key(&conn)?;
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    let version = migrations::current_version(&conn)?;
+    if integrity != "ok" || version > migrations::latest_version() {
+        error!(
+            "[Backup] Refusing to restore {:?}: integrity {}, version {}",
+            path, integrity, version
+        );
+        return Err(AppError::Validation(format!(
+            "Backup {:?} failed verification",
+            path
+        )));
+    }
+    Ok(())
+}
+pub fn restore_backup(name: &str) -> AppResult<()> {
+    let backup = list_backups()?
+        .into_iter()
+        .find(|info| info.name == name)
+        .ok_or_else(|| AppError::not_found(&format!("Backup '{}'", name)))?;
+    verify_backup(Path::new(&backup.path))?;
+    let safety = create_backup()?;
+    info!(
+        "[Backup] Saved current database as {} before restoring",
+        safety.name
+    );
+    db::with_conn(|conn| {
+        let src = Connection::open_with_flags(&backup.path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        db::apply_key(&src)?;
+        copy_database(&src, conn)?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        migrations::run(conn)
+    })?;
+    info!("[Backup] Restored {}", name);
+    Ok(())
+}
+pub fn retention_count() -> usize {
+    settings::get_integer(settings::BACKUP_RETENTION_COUNT).max(1) as usize
+}
+fn interval() -> Duration {
+    Duration::from_secs(
+        settings::get_integer(settings::BACKUP_INTERVAL_HOURS).max(1) as u64 * 60 * 60,
+    )
+}
+fn backup_due() -> bool {
+    let newest = list_backups()
+        .ok()
+        .and_then(|backups| backups.into_iter().next())
+        .and_then(|backup| fs::metadata(backup.path).ok())
+        .and_then(|metadata| metadata.modified().ok());
+    match newest {
+        Some(modified) => SystemTime::now()
+            .duration_since(modified)
+            .map(|age| age >= interval())
+            .unwrap_or(false),
+        None => true,
+    }
+}
+pub fn start_backup_scheduler() {
+    thread::spawn(|| loop {
+        if backup_due() {
+            match create_backup().and_then(|_| rotate_backups(retention_count())) {
+                Ok(removed) => info!("Created backup, removed {} old backups", removed),
+                Err(e) => error!("Failed to back up database: {:?}", e),
+            }
+        }
+        thread::sleep(CHECK_INTERVAL);
+    });
+}
@@ -0,0 +1,122 @@
+use crate::modules::db;
+use crate::modules::error::AppResult;
+use crate::modules::retention;
+use rusqlite::{OptionalExtension, Result, Transaction};
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    Moved,
+    Copied,
+    Deleted,
+    Unchanged,
+    Duplicate,
+    NotFound,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ItemOutcome {
+    pub id: i64,
+    pub status: ItemStatus,
+    pub new_id: Option<i64>,
+}
+fn active_directory_id(tx: &Transaction, id: i64) -> Result<Option<i64>> {
+    tx.prepare_cached(&format!(
+        "SELECT p.directory_id FROM {} WHERE p.id = ?1",
+        db::ITEM_SOURCE
+    ))?
+    .query_row([id], |row| row.get(0))
+    .optional()
+}
+fn duplicates_in_target(tx: &Transaction, id: i64, target_id: i64) -> Result<bool> {
+    let content: String = tx
+        .prepare_cached("SELECT content FROM paste_sheets WHERE id = ?1")?
+        .query_row([id], |row| row.get(0))?;
+    db::has_duplicate(tx, target_id, &content, Some(id))
+}
+// The target directory is checked inside the batch transaction, so it cannot be trashed
+// between the check and the writes. Its retention policy is applied before the commit.
+fn apply<F>(ids: &[i64], target_id: Option<i64>, mut f: F) -> AppResult<Vec<ItemOutcome>>
+where
+    F: FnMut(&Transaction, i64, i64, i64) -> Result<ItemOutcome>,
+{
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        if let Some(target_id) = target_id {
+            db::directory_name(&tx, target_id)?;
+        }
+        let mut outcomes = Vec::with_capacity(ids.len());
+        for &id in ids {
+            let outcome = match active_directory_id(&tx, id)? {
+                Some(directory_id) => f(&tx, id, directory_id, target_id.unwrap_or(directory_id))?,
+                None => ItemOutcome {
+                    id,
+                    status: ItemStatus::NotFound,
+                    new_id: None,
+                },
+            };
+            outcomes.push(outcome);
+        }
+        if let Some(target_id) = target_id {
+            retention::enforce(&tx, target_id)?;
+        }
+        tx.commit()?;
+        Ok(outcomes)
+    })
+}
+pub fn move_items(ids: &[i64], directory_id: i64) -> AppResult<Vec<ItemOutcome>> {
+    apply(ids, Some(directory_id), |tx, id, current_id, target_id| {
+        let status = if current_id == target_id {
+            ItemStatus::Unchanged
+        } else if duplicates_in_target(tx, id, target_id)? {
+            ItemStatus::Duplicate
+        } else {
+            tx.prepare_cached(
+                "UPDATE paste_sheets SET directory_id = ?1, updated_at = CURRENT_TIMESTAMP
+                 WHERE id = ?2",
+            )?
+            .execute([target_id, id])?;
+            ItemStatus::Moved
+        };
+        Ok(ItemOutcome {
+            id,
+            status,
+            new_id: None,
+        })
+    })
+}
+pub fn copy_items(ids: &[i64], directory_id: i64) -> AppResult<Vec<ItemOutcome>> {
+    apply(ids, Some(directory_id), |tx, id, _, target_id| {
+        if duplicates_in_target(tx, id, target_id)? {
+            return Ok(ItemOutcome {
+                id,
+                status: ItemStatus::Duplicate,
+                new_id: None,
+            });
+        }
+        tx.prepare_cached(
+            "INSERT INTO paste_sheets (content, content_hash, directory_id, memo)
+             SELECT content, content_hash, ?1, memo FROM paste_sheets WHERE id = ?2",
+        )?
+        .execute([target_id, id])?;
+        let new_id = tx.last_insert_rowid();
+        tx.prepare_cached(
+            "INSERT INTO item_tags (item_id, tag_id) SELECT ?1, tag_id FROM item_tags WHERE item_id = ?2",
+        )?
+        .execute([new_id, id])?;
+        Ok(ItemOutcome {
+            id,
+            status: ItemStatus::Copied,
+            new_id: Some(new_id),
+        })
+    })
+}
+pub fn delete_items(ids: &[i64]) -> AppResult<Vec<ItemOutcome>> {
+    apply(ids, None, |tx, id, _, _| {
+        tx.prepare_cached("UPDATE paste_sheets SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?1")?
+            .execute([id])?;
+        Ok(ItemOutcome {
+            id,
+            status: ItemStatus::Deleted,
+            new_id: None,
+        })
+    })
+}
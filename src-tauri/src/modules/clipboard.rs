@@ -1,5 +1,6 @@
 use crate::modules::db;
-use crate::modules::db::{find_by_content, update_content};
+use crate::modules::db::{find_by_content, touch_item};
+use crate::modules::error::{AppError, AppResult};
 use crate::modules::hotkey::restore_prev_app_native;
 use arboard::Clipboard;
 use enigo::{
@@ -7,34 +8,10 @@ use enigo::{
     Enigo, Key, Keyboard, Settings,
 };
 use log::{debug, error, info};
-use rusqlite::Connection;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
-const CLIPBOARD_DEFAULT_DIRECTORY: &str = "Clipboard";
-const MAX_ITEMS_PER_DIRECTORY: i64 = 30;
 const POLLING_INTERVAL: u64 = 100;
-pub fn cleanup_old_items(directory: &str) -> Result<(), rusqlite::Error> {
-    let conn = Connection::open(db::get_path())?;
-    let count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM paste_sheets WHERE directory = ?1",
-        [directory],
-        |row| row.get(0),
-    )?;
-    if count > MAX_ITEMS_PER_DIRECTORY {
-        let excess = count - MAX_ITEMS_PER_DIRECTORY;
-        conn.execute(
-            "DELETE FROM paste_sheets WHERE id IN (
-                SELECT id FROM paste_sheets
-                WHERE directory = ?1
-                ORDER BY created_at ASC
-                LIMIT ?2
-            )",
-            rusqlite::params![directory, excess],
-        )?;
-    }
-    Ok(())
-}
 pub fn get_clipboard_text() -> Option<String> {
     match Clipboard::new() {
         Ok(mut clipboard) => match clipboard.get_text() {
@@ -58,32 +35,25 @@ pub fn monitor_clipboard(app_handle: tauri::AppHandle) {
                 if current_text != *last && !current_text.trim().is_empty() {
                     info!("Clipboard content changed: {}", current_text.len());
                     let mut changed = false;
-                    match find_by_content(&current_text, CLIPBOARD_DEFAULT_DIRECTORY) {
-                        Ok(Some(existing_item)) => {
+                    let clipboard_id = db::with_conn(|conn| db::clipboard_directory_id(conn));
+                    let existing = clipboard_id
+                        .and_then(|id| find_by_content(&current_text, id).map(|item| (id, item)));
+                    match existing {
+                        Ok((_, Some(existing_item))) => {
                             info!("Updated existing clipboard content: {:?}", current_text);
-                            if let Err(e) = update_content(
-                                existing_item.id,
-                                &current_text,
-                                CLIPBOARD_DEFAULT_DIRECTORY,
-                                existing_item.memo.as_deref(),
-                            ) {
+                            if let Err(e) = touch_item(existing_item.id) {
                                 error!("Failed to update content: {:?}", e);
                             } else {
                                 changed = true;
                             }
                         }
-                        Ok(None) => {
-                            if let Err(e) =
-                                db::post_content(&current_text, CLIPBOARD_DEFAULT_DIRECTORY, None)
-                            {
+                        Ok((directory_id, None)) => {
+                            if let Err(e) = db::post_content(&current_text, directory_id, None) {
                                 error!("Failed to save to database: {:?}", e);
                             } else {
                                 debug!("Saved new content to database");
                                 changed = true;
                             }
-                            if let Err(e) = cleanup_old_items(CLIPBOARD_DEFAULT_DIRECTORY) {
-                                error!("Failed to cleanup old items: {:?}", e);
-                            }
                         }
                         Err(e) => {
                             error!("Failed to check content: {:?}", e);
@@ -101,28 +71,29 @@ pub fn monitor_clipboard(app_handle: tauri::AppHandle) {
         }
     });
 }
-pub fn paste_text(text: String) -> Result<(), String> {
-    let mut clipboard =
-        Clipboard::new().map_err(|e| format!("Failed to create clipboard: {:?}", e))?;
+fn paste_error(e: impl std::fmt::Display) -> AppError {
+    AppError::Paste(e.to_string())
+}
+pub fn paste_text(text: String) -> AppResult<()> {
+    let mut clipboard = Clipboard::new()
+        .map_err(|e| AppError::Clipboard(format!("Failed to create clipboard: {:?}", e)))?;
     clipboard
         .set_text(text)
-        .map_err(|e| format!("Failed to set clipboard text: {:?}", e))?;
+        .map_err(|e| AppError::Clipboard(format!("Failed to set clipboard text: {:?}", e)))?;
     info!("Text copied to clipbaord");
     restore_prev_app_native();
-    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    let mut enigo = Enigo::new(&Settings::default()).map_err(paste_error)?;
     #[cfg(target_os = "macos")]
     {
-        enigo.key(Key::Meta, Press).map_err(|e| e.to_string())?;
-        enigo.raw(9, Click).map_err(|e| e.to_string())?;
-        enigo.key(Key::Meta, Release).map_err(|e| e.to_string())?;
+        enigo.key(Key::Meta, Press).map_err(paste_error)?;
+        enigo.raw(9, Click).map_err(paste_error)?;
+        enigo.key(Key::Meta, Release).map_err(paste_error)?;
     }
     #[cfg(target_os = "windows")]
     {
-        enigo.key(Key::Control, Press).map_err(|e| e.to_string())?;
-        enigo.raw(86, Click).map_err(|e| e.to_string())?;
-        enigo
-            .key(Key::Control, Release)
-            .map_err(|e| e.to_string())?;
+        enigo.key(Key::Control, Press).map_err(paste_error)?;
+        enigo.raw(86, Click).map_err(paste_error)?;
+        enigo.key(Key::Control, Release).map_err(paste_error)?;
     }
     Ok(())
 }
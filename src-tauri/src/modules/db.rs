@@ -1,8 +1,44 @@
-use rusqlite::{Connection, Result};
+use crate::modules::error::{AppError, AppResult};
+use crate::modules::frecency;
+use crate::modules::location;
+use crate::modules::retention;
+use once_cell::sync::OnceCell;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::{ToSqlOutput, Value, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Result, Row, ToSql};
+use std::sync::Mutex;
+use std::time::Duration;
+const BUSY_TIMEOUT_MS: u64 = 5000;
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+static DB: OnceCell<Mutex<Connection>> = OnceCell::new();
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+pub const ITEM_COLUMNS: &str =
+    "p.id, p.content, d.name, p.created_at, p.memo, p.directory_id, p.pinned,
+    p.updated_at, p.last_used_at, p.use_count,
+    (SELECT group_concat(t.name, char(31)) FROM item_tags it JOIN tags t ON t.id = it.tag_id
+     WHERE it.item_id = p.id) AS tags";
+pub const ITEM_SOURCE: &str = "paste_sheets p JOIN directories d
+    ON d.id = p.directory_id AND d.deleted_at IS NULL AND p.deleted_at IS NULL";
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct DirectoryInfo {
+    pub id: i64,
     pub name: String,
+    pub parent_id: Option<i64>,
     pub count: i64,
+    pub total_count: i64,
+    pub unique_content: bool,
+}
+#[derive(Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildPolicy {
+    #[default]
+    Reject,
+    Cascade,
+    Reparent,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct PasteItem {
@@ -11,218 +47,771 @@ pub struct PasteItem {
     pub directory: String,
     pub created_at: String,
     pub memo: Option<String>,
+    pub directory_id: i64,
+    pub pinned: bool,
+    pub updated_at: String,
+    pub last_used_at: Option<String>,
+    pub use_count: i64,
+    pub tags: Vec<String>,
 }
-pub fn get_directories() -> Result<Vec<DirectoryInfo>> {
-    let conn = Connection::open(get_path())?;
-    let mut stmt = conn.prepare(
-        "SELECT d.name, COUNT(p.id) as count
-         FROM directories d
-         LEFT JOIN paste_sheets p ON d.name = p.directory
-         GROUP BY d.name
-         ORDER BY CASE WHEN d.name = 'Clipboard' THEN 0 ELSE 1 END, d.created_at",
-    )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(DirectoryInfo {
-            name: row.get(0)?,
-            count: row.get(1)?,
+#[derive(Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistorySort {
+    CreatedAt,
+    #[default]
+    UpdatedAt,
+    LastUsedAt,
+    UseCount,
+    Frecency,
+    Position,
+}
+impl HistorySort {
+    fn expression(self) -> &'static str {
+        match self {
+            HistorySort::CreatedAt => "p.created_at",
+            HistorySort::UpdatedAt => "p.updated_at",
+            HistorySort::LastUsedAt => "COALESCE(p.last_used_at, '')",
+            HistorySort::UseCount => "p.use_count",
+            HistorySort::Frecency => "COALESCE(p.frecency, 0.0)",
+            HistorySort::Position => "-p.position",
+        }
+    }
+    fn default_for(directory: Option<&str>) -> HistorySort {
+        match directory {
+            Some(name) if name != "Clipboard" => HistorySort::Position,
+            _ => HistorySort::default(),
+        }
+    }
+}
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum SortKey {
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+impl ToSql for SortKey {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        match self {
+            SortKey::Integer(value) => Ok(ToSqlOutput::Borrowed(ValueRef::Integer(*value))),
+            SortKey::Real(value) => Ok(ToSqlOutput::Borrowed(ValueRef::Real(*value))),
+            SortKey::Text(value) => Ok(ToSqlOutput::Borrowed(ValueRef::Text(value.as_bytes()))),
+        }
+    }
+}
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct HistoryCursor {
+    pub pinned: bool,
+    pub sort_key: SortKey,
+    pub id: i64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct HistoryPage {
+    pub items: Vec<PasteItem>,
+    pub next_cursor: Option<HistoryCursor>,
+}
+pub fn map_paste_item(row: &Row) -> Result<PasteItem> {
+    Ok(PasteItem {
+        id: row.get(0)?,
+        content: row.get(1)?,
+        directory: row.get(2)?,
+        created_at: row.get(3)?,
+        memo: row.get(4)?,
+        directory_id: row.get(5)?,
+        pinned: row.get(6)?,
+        updated_at: row.get(7)?,
+        last_used_at: row.get(8)?,
+        use_count: row.get(9)?,
+        tags: split_tags(row.get(10)?),
+    })
+}
+fn split_tags(joined: Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> = joined
+        .map(|s| s.split('\u{1f}').map(String::from).collect())
+        .unwrap_or_default();
+    tags.sort_by_key(|tag| tag.to_lowercase());
+    tags
+}
+pub fn passphrase() -> Option<String> {
+    PASSPHRASE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+pub fn set_passphrase(passphrase: Option<&str>) {
+    *PASSPHRASE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = passphrase.map(String::from);
+}
+pub fn apply_key(conn: &Connection) -> Result<()> {
+    match passphrase() {
+        Some(passphrase) => conn.pragma_update(None, "key", passphrase),
+        None => Ok(()),
+    }
+}
+// FNV-1a keeps stored hashes stable across Rust releases, unlike `DefaultHasher`.
+pub fn content_hash(content: &str) -> i64 {
+    content.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    }) as i64
+}
+// Only counts when the directory has unique content enabled; `except` skips the item
+// being edited or moved.
+pub fn has_duplicate(
+    conn: &Connection,
+    directory_id: i64,
+    content: &str,
+    except: Option<i64>,
+) -> Result<bool> {
+    conn.prepare_cached(
+        "SELECT EXISTS (
+            SELECT 1 FROM directories d JOIN paste_sheets p ON p.directory_id = d.id
+            WHERE d.id = ?1 AND d.unique_content AND p.deleted_at IS NULL
+              AND p.content_hash = ?2 AND p.content = ?3 AND p.id IS NOT ?4
+         )",
+    )?
+    .query_row(
+        rusqlite::params![directory_id, content_hash(content), content, except],
+        |row| row.get(0),
+    )
+}
+// Rows written by other tools arrive without a hash.
+pub fn fill_content_hashes(conn: &Connection) -> Result<usize> {
+    let rows: Vec<(i64, String)> = {
+        let mut stmt =
+            conn.prepare("SELECT id, content FROM paste_sheets WHERE content_hash IS NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_>>()?
+    };
+    let mut update = conn.prepare("UPDATE paste_sheets SET content_hash = ?1 WHERE id = ?2")?;
+    for (id, content) in &rows {
+        update.execute(rusqlite::params![content_hash(content), id])?;
+    }
+    Ok(rows.len())
+}
+// Only the content_hashes migration still backfills through SQL.
+pub fn register_functions(conn: &Connection) -> Result<()> {
+    conn.create_scalar_function(
+        "content_hash",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| match ctx.get_raw(0) {
+            ValueRef::Text(bytes) => Ok(Some(content_hash(&String::from_utf8_lossy(bytes)))),
+            _ => Ok(None),
+        },
+    )
+}
+pub fn is_initialized() -> bool {
+    DB.get().is_some()
+}
+pub fn install_connection(conn: Connection) {
+    if let Err(conn) = DB.set(Mutex::new(conn)) {
+        with_conn(|current| {
+            *current = conn
+                .into_inner()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            Ok::<_, rusqlite::Error>(())
         })
-    })?;
-    let mut result = Vec::new();
-    for row in rows {
-        result.push(row?);
+        .ok();
     }
-    Ok(result)
 }
-pub fn get_path() -> String {
-    let mut path = dirs::data_dir().unwrap();
-    path.push("paste_sheets.db");
-    path.to_str().unwrap().to_string()
-}
-pub fn init_db() -> Result<Connection> {
-    let conn = Connection::open(get_path())?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS directories (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )?;
-    conn.execute(
-        "INSERT OR IGNORE INTO directories (name) VALUES ('Clipboard')",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS paste_sheets (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            content TEXT NOT NULL,
-            directory TEXT NOT NULL,
-            memo TEXT,
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (directory) REFERENCES directories(name)
-        )",
-        [],
-    )?;
-    let has_memo = {
-        let mut stmt = conn.prepare("PRAGMA table_info(paste_sheets)")?;
+pub fn open_connection(path: &str) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    apply_key(&conn)?;
+    conn.busy_timeout(Duration::from_millis(BUSY_TIMEOUT_MS))?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    register_functions(&conn)?;
+    Ok(conn)
+}
+pub fn with_conn<T, E, F>(f: F) -> std::result::Result<T, E>
+where
+    E: From<rusqlite::Error>,
+    F: FnOnce(&mut Connection) -> std::result::Result<T, E>,
+{
+    let db = DB.get_or_try_init(|| open_connection(&get_path()).map(Mutex::new))?;
+    let mut conn = db.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // Recovery and re-keying park an in-memory placeholder while they swap files. If they
+    // could not reopen the real file afterwards, retry here rather than serve the placeholder.
+    if conn.path() == Some("") {
+        *conn = open_connection(&get_path())?;
+    }
+    f(&mut conn)
+}
+pub fn get_directories() -> AppResult<Vec<DirectoryInfo>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare_cached(
+            "WITH RECURSIVE
+             counts(directory_id, count) AS (
+                SELECT directory_id, COUNT(*) FROM paste_sheets
+                WHERE deleted_at IS NULL GROUP BY directory_id
+             ),
+             subtree(root_id, id) AS (
+                SELECT id, id FROM directories WHERE deleted_at IS NULL
+                UNION ALL
+                SELECT s.root_id, d.id FROM subtree s
+                JOIN directories d ON d.parent_id = s.id AND d.deleted_at IS NULL
+             ),
+             tree(id, path) AS (
+                SELECT id, printf('%d/%010d.%010d', name != 'Clipboard', position, id)
+                FROM directories WHERE parent_id IS NULL AND deleted_at IS NULL
+                UNION ALL
+                SELECT d.id, t.path || printf('/%010d.%010d', d.position, d.id) FROM tree t
+                JOIN directories d ON d.parent_id = t.id AND d.deleted_at IS NULL
+             )
+             SELECT d.id, d.name, d.parent_id, d.unique_content,
+                COALESCE((SELECT count FROM counts WHERE directory_id = d.id), 0) as count,
+                (SELECT COALESCE(SUM(c.count), 0) FROM subtree s
+                 JOIN counts c ON c.directory_id = s.id WHERE s.root_id = d.id) as total_count
+             FROM tree t JOIN directories d ON d.id = t.id
+             ORDER BY t.path",
+        )?;
         let rows = stmt.query_map([], |row| {
-            let name: String = row.get(1)?;
-            Ok(name)
+            Ok(DirectoryInfo {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                parent_id: row.get(2)?,
+                unique_content: row.get(3)?,
+                count: row.get(4)?,
+                total_count: row.get(5)?,
+            })
         })?;
-        let mut found = false;
+        let mut result = Vec::new();
         for row in rows {
-            if let Ok(name) = row {
-                if name == "memo" {
-                    found = true;
-                    break;
-                }
-            }
+            result.push(row?);
         }
-        found
+        Ok(result)
+    })
+}
+pub fn get_path() -> String {
+    location::current().database_path
+}
+// Clipboard is the only name that cannot repeat, since it is reserved everywhere and
+// always top-level. Every other directory is addressed by id.
+pub fn clipboard_directory_id(conn: &Connection) -> AppResult<i64> {
+    conn.prepare_cached(
+        "SELECT id FROM directories WHERE name = 'Clipboard' AND parent_id IS NULL",
+    )?
+    .query_row([], |row| row.get(0))
+    .optional()?
+    .ok_or_else(|| AppError::not_found("Directory 'Clipboard'"))
+}
+pub fn directory_name(conn: &Connection, id: i64) -> AppResult<String> {
+    conn.prepare_cached("SELECT name FROM directories WHERE id = ?1 AND deleted_at IS NULL")?
+        .query_row([id], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| AppError::not_found("Directory"))
+}
+fn directory_conflict(error: rusqlite::Error, name: &str) -> AppError {
+    match AppError::from(error) {
+        AppError::Duplicate(_) => {
+            AppError::Duplicate(format!("Directory '{}' already exists", name))
+        }
+        other => other,
+    }
+}
+fn find_parent_id(conn: &Connection, parent_id: Option<i64>) -> AppResult<Option<i64>> {
+    let Some(parent_id) = parent_id else {
+        return Ok(None);
     };
-    if !has_memo {
-        conn.execute("ALTER TABLE paste_sheets ADD COLUMN memo TEXT", [])?;
+    if directory_name(conn, parent_id)? == "Clipboard" {
+        return Err(AppError::ReservedName(
+            "Clipboard cannot contain sub-directories".to_string(),
+        ));
     }
-    conn.execute(
-        "INSERT OR IGNORE INTO directories (name)
-         SELECT DISTINCT directory FROM paste_sheets",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS settings (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL
-        )",
-        [],
-    )?;
-    conn.execute(
-        "INSERT OR IGNORE INTO settings (key, value) VALUES ('mouse_edge_enabled', 'true')",
-        [],
-    )?;
-    Ok(conn)
+    Ok(Some(parent_id))
 }
-pub fn create_directory(name: &str) -> Result<i64> {
+pub fn create_directory(name: &str, parent_id: Option<i64>) -> AppResult<i64> {
     let trimmed_name = name.trim();
     if trimmed_name.is_empty() {
-        return Err(rusqlite::Error::InvalidQuery);
+        return Err(AppError::Validation(
+            "Directory name cannot be empty".to_string(),
+        ));
+    }
+    if trimmed_name == "Clipboard" {
+        return Err(AppError::reserved(trimmed_name));
     }
-    let conn = Connection::open(get_path())?;
-    conn.execute("INSERT INTO directories (name) VALUES (?1)", [trimmed_name])?;
-    Ok(conn.last_insert_rowid())
+    with_conn(|conn| {
+        let parent_id = find_parent_id(conn, parent_id)?;
+        conn.prepare_cached("INSERT INTO directories (name, parent_id) VALUES (?1, ?2)")?
+            .execute(rusqlite::params![trimmed_name, parent_id])
+            .map_err(|e| directory_conflict(e, trimmed_name))?;
+        Ok(conn.last_insert_rowid())
+    })
 }
-pub fn rename_directory(old_name: &str, new_name: &str) -> Result<()> {
-    let old_trimmed = old_name.trim();
+pub fn move_directory(id: i64, parent_id: Option<i64>) -> AppResult<()> {
+    with_conn(|conn| {
+        let name = directory_name(conn, id)?;
+        if name == "Clipboard" {
+            return Err(AppError::reserved(&name));
+        }
+        let parent_id = find_parent_id(conn, parent_id)?;
+        if let Some(parent_id) = parent_id {
+            let creates_cycle: bool = conn.query_row(
+                "WITH RECURSIVE subtree(id) AS (
+                    SELECT ?1
+                    UNION ALL
+                    SELECT d.id FROM directories d JOIN subtree s ON d.parent_id = s.id
+                 )
+                 SELECT EXISTS (SELECT 1 FROM subtree WHERE id = ?2)",
+                [id, parent_id],
+                |row| row.get(0),
+            )?;
+            if creates_cycle {
+                return Err(AppError::Validation(format!(
+                    "Cannot move '{}' into itself or one of its sub-directories",
+                    name
+                )));
+            }
+        }
+        conn.execute(
+            "UPDATE directories
+             SET parent_id = ?1,
+                 position = (
+                     SELECT COALESCE(MAX(position), 0) + 1 FROM directories WHERE parent_id IS ?1
+                 )
+             WHERE id = ?2 AND parent_id IS NOT ?1",
+            rusqlite::params![parent_id, id],
+        )
+        .map_err(|e| directory_conflict(e, &name))?;
+        Ok(())
+    })
+}
+pub fn rename_directory(id: i64, new_name: &str) -> AppResult<()> {
     let new_trimmed = new_name.trim();
-    if old_trimmed == "Clipboard" || new_trimmed == "Clipboard" || new_trimmed.is_empty() {
-        return Err(rusqlite::Error::InvalidQuery);
-    }
-    log::info!("[DB] Rename start: '{}' -> '{}'", old_trimmed, new_trimmed);
-    let mut conn = Connection::open(get_path())?;
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
-    let tx = conn.transaction()?;
-    let affected_dirs = tx.execute(
-        "UPDATE directories SET name = ?1 WHERE name = ?2",
-        [new_trimmed, old_trimmed],
-    )?;
-    log::info!("[DB] Affected directories: {}", affected_dirs);
-    if affected_dirs == 0 {
-        return Err(rusqlite::Error::QueryReturnedNoRows);
+    if new_trimmed == "Clipboard" {
+        return Err(AppError::reserved("Clipboard"));
     }
-    let affected_items = tx.execute(
-        "UPDATE paste_sheets SET directory = ?1 WHERE directory = ?2",
-        [new_trimmed, old_trimmed],
-    )?;
-    log::info!("[DB] Affected paste items: {}", affected_items);
-    tx.commit()?;
-    conn.execute("PRAGMA foreign_keys = ON", [])?;
-    log::info!("[DB] Rename committed successfully");
-    Ok(())
-}
-pub fn delete_directory(name: &str) -> Result<()> {
-    if name == "Clipboard" {
-        return Err(rusqlite::Error::InvalidQuery);
-    }
-    let conn = Connection::open(get_path())?;
-    conn.execute("DELETE FROM paste_sheets WHERE directory = ?1", [name])?;
-    conn.execute("DELETE FROM directories WHERE name = ?1", [name])?;
-    Ok(())
-}
-pub fn post_content(content: &str, directory: &str, memo: Option<&str>) -> Result<i64> {
-    let conn = Connection::open(get_path())?;
-    conn.execute(
-        "INSERT INTO paste_sheets (content, directory, memo) VALUES (?1, ?2, ?3)",
-        rusqlite::params![content, directory, memo],
-    )?;
-    Ok(conn.last_insert_rowid())
+    if new_trimmed.is_empty() {
+        return Err(AppError::Validation(
+            "Directory name cannot be empty".to_string(),
+        ));
+    }
+    with_conn(|conn| {
+        let old_name = directory_name(conn, id)?;
+        if old_name == "Clipboard" {
+            return Err(AppError::reserved("Clipboard"));
+        }
+        log::info!("[DB] Rename start: '{}' -> '{}'", old_name, new_trimmed);
+        conn.execute(
+            "UPDATE directories SET name = ?1 WHERE id = ?2",
+            rusqlite::params![new_trimmed, id],
+        )
+        .map_err(|e| directory_conflict(e, new_trimmed))?;
+        log::info!("[DB] Rename committed successfully");
+        Ok(())
+    })
+}
+pub fn set_unique_content(id: i64, enabled: bool) -> AppResult<()> {
+    with_conn(|conn| {
+        let name = directory_name(conn, id)?;
+        if enabled {
+            let has_duplicates: bool = conn.query_row(
+                "SELECT EXISTS (
+                    SELECT 1 FROM paste_sheets
+                    WHERE directory_id = ?1 AND deleted_at IS NULL
+                    GROUP BY content_hash, content HAVING COUNT(*) > 1
+                 )",
+                [id],
+                |row| row.get(0),
+            )?;
+            if has_duplicates {
+                return Err(AppError::Duplicate(format!(
+                    "Directory '{}' already contains duplicate items",
+                    name
+                )));
+            }
+        }
+        conn.execute(
+            "UPDATE directories SET unique_content = ?1 WHERE id = ?2",
+            rusqlite::params![enabled, id],
+        )?;
+        Ok(())
+    })
+}
+pub fn delete_directory(id: i64, children: ChildPolicy) -> AppResult<()> {
+    with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let name = directory_name(&tx, id)?;
+        if name == "Clipboard" {
+            return Err(AppError::reserved(&name));
+        }
+        match children {
+            ChildPolicy::Reject => {
+                let has_children: bool = tx.query_row(
+                    "SELECT EXISTS (
+                        SELECT 1 FROM directories WHERE parent_id = ?1 AND deleted_at IS NULL
+                     )",
+                    [id],
+                    |row| row.get(0),
+                )?;
+                if has_children {
+                    return Err(AppError::Validation(format!(
+                        "Directory '{}' still has sub-directories",
+                        name
+                    )));
+                }
+            }
+            ChildPolicy::Cascade => {}
+            ChildPolicy::Reparent => {
+                tx.execute(
+                    "UPDATE directories
+                     SET parent_id = (SELECT parent_id FROM directories WHERE id = ?1)
+                     WHERE parent_id = ?1",
+                    [id],
+                )
+                .map_err(|e| match AppError::from(e) {
+                    AppError::Duplicate(_) => AppError::Duplicate(format!(
+                        "A sub-directory of '{}' has the same name as one of its new siblings",
+                        name
+                    )),
+                    other => other,
+                })?;
+            }
+        }
+        // Millisecond timestamps let a restore tell this subtree apart from children
+        // that were trashed on their own earlier.
+        tx.execute(
+            "WITH RECURSIVE subtree(id) AS (
+                SELECT ?1
+                UNION ALL
+                SELECT d.id FROM directories d JOIN subtree s ON d.parent_id = s.id
+                WHERE d.deleted_at IS NULL
+             )
+             UPDATE directories SET deleted_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+             WHERE id IN subtree AND deleted_at IS NULL",
+            [id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+}
+pub fn post_content(content: &str, directory_id: i64, memo: Option<&str>) -> AppResult<i64> {
+    with_conn(|conn| {
+        directory_name(conn, directory_id)?;
+        if has_duplicate(conn, directory_id, content, None)? {
+            return Err(AppError::duplicate_content());
+        }
+        conn.prepare_cached(
+            "INSERT INTO paste_sheets (content, content_hash, directory_id, memo)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?
+        .execute(rusqlite::params![
+            content,
+            content_hash(content),
+            directory_id,
+            memo
+        ])?;
+        let id = conn.last_insert_rowid();
+        retention::enforce(conn, directory_id)?;
+        Ok(id)
+    })
+}
+pub fn get_all_contents() -> AppResult<Vec<PasteItem>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM {} ORDER BY p.pinned DESC, p.updated_at DESC, p.id DESC",
+            ITEM_COLUMNS, ITEM_SOURCE
+        ))?;
+        let rows = stmt.query_map([], map_paste_item)?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    })
+}
+pub fn get_directory_contents(
+    directory_id: i64,
+    sort: Option<HistorySort>,
+) -> AppResult<Vec<PasteItem>> {
+    with_conn(|conn| {
+        let name = directory_name(conn, directory_id)?;
+        let sort = sort.unwrap_or_else(|| HistorySort::default_for(Some(&name)));
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM {} WHERE p.directory_id = ?1
+             ORDER BY p.pinned DESC, {} DESC, p.id DESC",
+            ITEM_COLUMNS,
+            ITEM_SOURCE,
+            sort.expression()
+        ))?;
+        let rows = stmt.query_map([directory_id], map_paste_item)?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    })
+}
+pub fn get_contents_page(
+    directory_id: Option<i64>,
+    tags: &[String],
+    sort: Option<HistorySort>,
+    limit: Option<i64>,
+    cursor: Option<&HistoryCursor>,
+) -> AppResult<HistoryPage> {
+    with_conn(|conn| contents_page(conn, directory_id, tags, sort, limit, cursor))
 }
-pub fn get_all_contents() -> Result<Vec<PasteItem>> {
-    let conn = Connection::open(get_path())?;
-    let mut stmt = conn.prepare(
-        "SELECT id, content, directory, created_at, memo FROM paste_sheets ORDER BY created_at DESC",
+fn contents_page(
+    conn: &Connection,
+    directory_id: Option<i64>,
+    tags: &[String],
+    sort: Option<HistorySort>,
+    limit: Option<i64>,
+    cursor: Option<&HistoryCursor>,
+) -> AppResult<HistoryPage> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let mut tags: Vec<String> = tags.iter().map(|t| t.trim().to_ascii_lowercase()).collect();
+    tags.sort();
+    tags.dedup();
+    let tags_json = serde_json::to_string(&tags)?;
+    let sort = match sort {
+        Some(sort) => sort,
+        None => {
+            let name = directory_id
+                .map(|id| directory_name(conn, id))
+                .transpose()?;
+            HistorySort::default_for(name.as_deref())
+        }
+    };
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {columns}, {sort} AS sort_key FROM {source}
+         WHERE (?1 IS NULL OR p.directory_id = ?1)
+           AND (?2 IS NULL OR (p.pinned, {sort}, p.id) < (?2, ?3, ?4))
+           AND (?6 = 0 OR ?6 = (
+               SELECT COUNT(*) FROM item_tags it JOIN tags t ON t.id = it.tag_id
+               WHERE it.item_id = p.id AND t.name IN (SELECT value FROM json_each(?7))
+           ))
+         ORDER BY p.pinned DESC, {sort} DESC, p.id DESC
+         LIMIT ?5",
+        columns = ITEM_COLUMNS,
+        source = ITEM_SOURCE,
+        sort = sort.expression()
+    ))?;
+    let rows = stmt.query_map(
+        rusqlite::params![
+            directory_id,
+            cursor.map(|c| c.pinned),
+            cursor.map(|c| &c.sort_key),
+            cursor.map(|c| c.id),
+            limit + 1,
+            tags.len() as i64,
+            tags_json
+        ],
+        |row| {
+            let sort_key = match row.get::<_, Value>("sort_key")? {
+                Value::Integer(value) => SortKey::Integer(value),
+                Value::Real(value) => SortKey::Real(value),
+                Value::Text(value) => SortKey::Text(value),
+                _ => SortKey::Text(String::new()),
+            };
+            Ok((map_paste_item(row)?, sort_key))
+        },
     )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(PasteItem {
-            id: row.get(0)?,
-            content: row.get(1)?,
-            directory: row.get(2)?,
-            created_at: row.get(3)?,
-            memo: row.get(4)?,
-        })
-    })?;
-    let mut result = Vec::new();
+    let mut items = Vec::new();
+    let mut sort_keys = Vec::new();
     for row in rows {
-        result.push(row?);
+        let (item, sort_key) = row?;
+        items.push(item);
+        sort_keys.push(sort_key);
     }
-    Ok(result)
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        let sort_key = sort_keys.swap_remove(limit as usize - 1);
+        items.last().map(|item| HistoryCursor {
+            pinned: item.pinned,
+            sort_key,
+            id: item.id,
+        })
+    } else {
+        None
+    };
+    Ok(HistoryPage { items, next_cursor })
 }
-pub fn update_content(id: i64, content: &str, directory: &str, memo: Option<&str>) -> Result<i64> {
-    let conn = Connection::open(get_path())?;
-    conn.execute(
-        "UPDATE paste_sheets SET content = ?1, directory = ?2, memo = ?3, created_at = CURRENT_TIMESTAMP WHERE id = ?4",
-        rusqlite::params![content, directory, memo, id],
-    )?;
-    Ok(id)
+pub fn update_content(
+    id: i64,
+    content: &str,
+    directory_id: i64,
+    memo: Option<&str>,
+) -> AppResult<i64> {
+    with_conn(|conn| {
+        directory_name(conn, directory_id)?;
+        if has_duplicate(conn, directory_id, content, Some(id))? {
+            return Err(AppError::duplicate_content());
+        }
+        conn.prepare_cached(
+            "UPDATE paste_sheets SET content = ?1, content_hash = ?2, directory_id = ?3, memo = ?4, updated_at = CURRENT_TIMESTAMP WHERE id = ?5",
+        )?
+        .execute(rusqlite::params![
+            content,
+            content_hash(content),
+            directory_id,
+            memo,
+            id
+        ])?;
+        retention::enforce(conn, directory_id)?;
+        Ok(id)
+    })
 }
-pub fn find_by_content(content: &str, directory: &str) -> Result<Option<PasteItem>> {
-    let conn = Connection::open(get_path())?;
-    let mut stmt = conn.prepare(
-        "SELECT id, content, directory, created_at, memo FROM paste_sheets WHERE content = ?1 AND directory = ?2 LIMIT 1",
-    )?;
-    let result = stmt.query_row([content, directory], |row| {
-        Ok(PasteItem {
-            id: row.get(0)?,
-            content: row.get(1)?,
-            directory: row.get(2)?,
-            created_at: row.get(3)?,
-            memo: row.get(4)?,
-        })
-    });
-    match result {
-        Ok(item) => Ok(Some(item)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e),
-    }
-}
-pub fn delete_history_item(id: i64) -> Result<()> {
-    let conn = Connection::open(get_path())?;
-    conn.execute("DELETE FROM paste_sheets WHERE id = ?1", [id])?;
-    Ok(())
-}
-pub fn get_setting(key: &str) -> Result<Option<String>> {
-    let conn = Connection::open(get_path())?;
-    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
-    let result = stmt.query_row([key], |row| row.get(0));
-    match result {
-        Ok(val) => Ok(Some(val)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e),
-    }
-}
-pub fn set_setting(key: &str, value: &str) -> Result<()> {
-    let conn = Connection::open(get_path())?;
-    conn.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-        [key, value],
-    )?;
-    Ok(())
+pub fn get_item(id: i64) -> AppResult<PasteItem> {
+    with_conn(|conn| {
+        conn.prepare_cached(&format!(
+            "SELECT {} FROM {} WHERE p.id = ?1",
+            ITEM_COLUMNS, ITEM_SOURCE
+        ))?
+        .query_row([id], map_paste_item)
+        .optional()?
+        .ok_or_else(|| AppError::not_found("Item"))
+    })
+}
+pub fn touch_item(id: i64) -> AppResult<()> {
+    with_conn(|conn| {
+        conn.prepare_cached(
+            "UPDATE paste_sheets SET updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        )?
+        .execute([id])?;
+        Ok(())
+    })
+}
+pub fn record_use(id: i64) -> AppResult<()> {
+    with_conn(|conn| {
+        let previous: Option<f64> = conn
+            .prepare_cached(
+                "SELECT frecency FROM paste_sheets WHERE id = ?1 AND deleted_at IS NULL",
+            )?
+            .query_row([id], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| AppError::not_found("Item"))?;
+        conn.prepare_cached(
+            "UPDATE paste_sheets
+             SET last_used_at = CURRENT_TIMESTAMP, use_count = use_count + 1, frecency = ?1
+             WHERE id = ?2",
+        )?
+        .execute(rusqlite::params![
+            frecency::bump(previous, frecency::now()),
+            id
+        ])?;
+        Ok(())
+    })
+}
+pub fn set_pinned(id: i64, pinned: bool) -> AppResult<()> {
+    with_conn(|conn| {
+        let affected = conn
+            .prepare_cached(
+                "UPDATE paste_sheets SET pinned = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            )?
+            .execute(rusqlite::params![pinned, id])?;
+        if affected == 0 {
+            return Err(AppError::not_found("Item"));
+        }
+        Ok(())
+    })
+}
+pub fn find_by_content(content: &str, directory_id: i64) -> AppResult<Option<PasteItem>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM {}
+             WHERE p.directory_id = ?1 AND p.content_hash = ?2 AND p.content = ?3 LIMIT 1",
+            ITEM_COLUMNS, ITEM_SOURCE
+        ))?;
+        let item = stmt
+            .query_row(
+                rusqlite::params![directory_id, content_hash(content), content],
+                map_paste_item,
+            )
+            .optional()?;
+        Ok(item)
+    })
+}
+pub fn delete_history_item(id: i64) -> AppResult<()> {
+    with_conn(|conn| {
+        conn.prepare_cached(
+            "UPDATE paste_sheets SET deleted_at = CURRENT_TIMESTAMP
+             WHERE id = ?1 AND deleted_at IS NULL",
+        )?
+        .execute([id])?;
+        Ok(())
+    })
+}
+pub fn get_setting(key: &str) -> AppResult<Option<String>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare_cached("SELECT value FROM settings WHERE key = ?1")?;
+        let value = stmt.query_row([key], |row| row.get(0)).optional()?;
+        Ok(value)
+    })
+}
+pub fn set_setting(key: &str, value: &str) -> AppResult<()> {
+    with_conn(|conn| {
+        conn.prepare_cached("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)")?
+            .execute([key, value])?;
+        Ok(())
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::migrations;
+    // Not `open_connection`: that applies the process-wide passphrase, which the
+    // encryption tests set while these run.
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        register_functions(&conn).unwrap();
+        migrations::run(&mut conn).unwrap();
+        // Pins, last uses, frecency and positions all repeat, so only the id breaks ties.
+        conn.execute_batch(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 40)
+             INSERT INTO paste_sheets
+                (id, content, directory_id, pinned, last_used_at, frecency, position)
+             SELECT i, 'item ' || i, (SELECT id FROM directories WHERE name = 'Clipboard'),
+                i % 7 = 0,
+                CASE WHEN i % 3 = 0 THEN NULL ELSE printf('2026-01-0%d 10:00:00', i % 4 + 1) END,
+                CASE WHEN i % 5 = 0 THEN NULL ELSE (i % 4) * 1.5 END,
+                i % 6
+             FROM n",
+        )
+        .unwrap();
+        conn
+    }
+    fn all_pages(conn: &Connection, sort: HistorySort) -> Vec<i64> {
+        let mut ids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page =
+                contents_page(conn, None, &[], Some(sort), Some(3), cursor.as_ref()).unwrap();
+            ids.extend(page.items.iter().map(|item| item.id));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return ids,
+            }
+        }
+    }
+    fn assert_pages_match_single_query(sort: HistorySort) {
+        let conn = database();
+        let expected: Vec<i64> = contents_page(&conn, None, &[], Some(sort), Some(100), None)
+            .unwrap()
+            .items
+            .iter()
+            .map(|item| item.id)
+            .collect();
+        let mut unique = expected.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique, (1..=40).collect::<Vec<_>>());
+        assert!(expected[..5].iter().all(|id| id % 7 == 0));
+        assert_eq!(all_pages(&conn, sort), expected);
+    }
+    #[test]
+    fn frecency_pages_neither_skip_nor_repeat() {
+        assert_pages_match_single_query(HistorySort::Frecency);
+    }
+    #[test]
+    fn last_used_pages_neither_skip_nor_repeat() {
+        assert_pages_match_single_query(HistorySort::LastUsedAt);
+    }
+    #[test]
+    fn position_pages_neither_skip_nor_repeat() {
+        assert_pages_match_single_query(HistorySort::Position);
+    }
 }
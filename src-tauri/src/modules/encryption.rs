@@ -0,0 +1,341 @@
+use crate::modules::error::{AppError, AppResult};
+use crate::modules::maintenance::{self, RecoveryReport};
+use crate::modules::{backup, db, location, migrations};
+use log::{error, info};
+use rusqlite::{Connection, DatabaseName, ErrorCode, OpenFlags, Result};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+}
+// SQLCipher encrypts the whole file, header included, so a plaintext header means no key.
+pub fn is_encrypted(path: &str) -> bool {
+    let mut header = [0u8; 16];
+    match File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != PLAINTEXT_HEADER,
+        Err(_) => false,
+    }
+}
+// Only a database the user encrypted can be locked. An unreadable header on any other
+// database is damage, which the startup check recovers.
+pub fn is_locked() -> bool {
+    let path = db::get_path();
+    db::passphrase().is_none() && location::is_marked_encrypted(&path) && is_encrypted(&path)
+}
+pub fn status() -> EncryptionStatus {
+    let enabled = db::passphrase().is_some() || is_locked();
+    EncryptionStatus {
+        enabled,
+        unlocked: db::is_initialized() && !is_locked(),
+    }
+}
+fn validate(passphrase: &str) -> AppResult<()> {
+    if passphrase.is_empty() {
+        return Err(AppError::Validation(
+            "Passphrase cannot be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+fn check_current(passphrase: &str) -> AppResult<()> {
+    if db::passphrase().as_deref() != Some(passphrase) {
+        return Err(AppError::Validation(
+            "Current passphrase is incorrect".to_string(),
+        ));
+    }
+    Ok(())
+}
+// A wrong key and a damaged first page look the same to SQLCipher, so only that case is
+// reported as a bad passphrase. Anything else is left to the startup check to recover.
+fn check_key(path: &str) -> AppResult<()> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    db::apply_key(&conn)?;
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(())) {
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::NotADatabase) => {
+            Err(AppError::Validation("Passphrase is incorrect".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+pub fn unlock(passphrase: &str) -> AppResult<Option<RecoveryReport>> {
+    validate(passphrase)?;
+    if db::is_initialized() {
+        return check_current(passphrase).map(|_| None);
+    }
+    db::set_passphrase(Some(passphrase));
+    let unlocked = check_key(&db::get_path()).and_then(|_| maintenance::startup_check());
+    match unlocked {
+        Ok(report) => {
+            info!("[Encryption] Database unlocked");
+            Ok(report)
+        }
+        Err(e) => {
+            db::set_passphrase(None);
+            error!("[Encryption] Failed to unlock database: {:?}", e);
+            Err(e)
+        }
+    }
+}
+fn remove_database_files(path: &str) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", path, suffix));
+    }
+}
+fn export_to(conn: &Connection, staged: &str, passphrase: Option<&str>) -> Result<()> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+        [staged, passphrase.unwrap_or("")],
+    )?;
+    let exported = conn
+        .query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()))
+        .and_then(|_| {
+            let version = migrations::current_version(conn)?;
+            conn.pragma_update(
+                Some(DatabaseName::Attached("rekeyed")),
+                "user_version",
+                version,
+            )
+        });
+    conn.execute("DETACH DATABASE rekeyed", [])?;
+    exported
+}
+fn open_with_key(path: &str, flags: OpenFlags, key: Option<&str>) -> Result<Connection> {
+    let conn = Connection::open_with_flags(path, flags)?;
+    if let Some(key) = key {
+        conn.pragma_update(None, "key", key)?;
+    }
+    Ok(conn)
+}
+fn check_staged(staged: &str, passphrase: Option<&str>) -> AppResult<()> {
+    let conn = open_with_key(staged, OpenFlags::SQLITE_OPEN_READ_ONLY, passphrase)?;
+    let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(AppError::Storage(format!(
+            "Re-encrypted database failed verification: {}",
+            check
+        )));
+    }
+    Ok(())
+}
+// The live file is rewritten through sqlcipher_export into a staged copy that is checked
+// and then swapped in. The original stays next to it until the new file has been opened,
+// so any failure puts back the original database and passphrase.
+fn reencrypt_live(path: &str, passphrase: Option<&str>) -> AppResult<()> {
+    let staged = format!("{}.rekey", path);
+    let retired = format!("{}.retired", path);
+    db::with_conn(|conn| {
+        remove_database_files(&staged);
+        let exported = export_to(conn, &staged, passphrase)
+            .map_err(AppError::from)
+            .and_then(|_| check_staged(&staged, passphrase));
+        if let Err(e) = exported {
+            remove_database_files(&staged);
+            return Err(e);
+        }
+        let previous = std::mem::replace(conn, Connection::open_in_memory()?);
+        if let Err((previous, e)) = previous.close() {
+            *conn = previous;
+            remove_database_files(&staged);
+            return Err(e.into());
+        }
+        let _ = fs::remove_file(format!("{}-wal", path));
+        let _ = fs::remove_file(format!("{}-shm", path));
+        remove_database_files(&retired);
+        let previous_passphrase = db::passphrase();
+        let swapped = fs::rename(path, &retired)
+            .and_then(|_| fs::rename(&staged, path))
+            .map_err(AppError::from)
+            .and_then(|_| {
+                db::set_passphrase(passphrase);
+                Ok(db::open_connection(path)?)
+            });
+        match swapped {
+            Ok(fresh) => {
+                *conn = fresh;
+                remove_database_files(&retired);
+                Ok(())
+            }
+            Err(e) => {
+                error!(
+                    "[Encryption] Failed to swap in re-encrypted database: {:?}",
+                    e
+                );
+                // If the original cannot be moved back, it is opened where it was retired
+                // to, so the app keeps working on the real data either way.
+                let mut original = path.to_string();
+                if Path::new(&retired).exists() {
+                    remove_database_files(path);
+                    if let Err(restore) = fs::rename(&retired, path) {
+                        error!("[Encryption] Failed to put back {}: {:?}", retired, restore);
+                        original = retired;
+                    }
+                }
+                remove_database_files(&staged);
+                db::set_passphrase(previous_passphrase.as_deref());
+                match db::open_connection(&original) {
+                    Ok(previous) => *conn = previous,
+                    Err(reopen) => {
+                        error!("[Encryption] Failed to reopen {}: {:?}", original, reopen)
+                    }
+                }
+                Err(e)
+            }
+        }
+    })
+}
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct RekeyReport {
+    pub rekeyed: Vec<String>,
+    pub failed: Vec<String>,
+}
+// Copies made by older code may still be plaintext, so both keys are tried.
+fn rekey_copy(file: &str, previous: Option<&str>, passphrase: Option<&str>) -> AppResult<()> {
+    let staged = format!("{}.rekey", file);
+    let mut keys = vec![previous];
+    if previous.is_some() {
+        keys.push(None);
+    }
+    let mut result = Ok(());
+    for key in keys {
+        remove_database_files(&staged);
+        result = open_with_key(file, OpenFlags::default(), key).and_then(|conn| {
+            export_to(&conn, &staged, passphrase)?;
+            conn.close().map_err(|(_, e)| e)
+        });
+        if result.is_ok() {
+            break;
+        }
+    }
+    if let Err(e) = result {
+        remove_database_files(&staged);
+        return Err(e.into());
+    }
+    // The rename replaces the file in one step, so a crash leaves either the old or the
+    // new copy. Only a leftover journal of the old copy has to go first.
+    let _ = fs::remove_file(format!("{}-wal", file));
+    let _ = fs::remove_file(format!("{}-shm", file));
+    Ok(fs::rename(&staged, file)?)
+}
+// Damaged files moved aside by recovery sit next to the database as <name>.corrupt-<secs>.
+fn corrupt_copies(path: &str) -> Vec<String> {
+    let path = Path::new(path);
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.corrupt-", name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .strip_prefix(&prefix)
+                .is_some_and(|stamp| stamp.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(|entry| entry.path().to_string_lossy().to_string())
+        .collect()
+}
+// Backups and corrupt copies are re-keyed too, otherwise they would stay readable without
+// the new passphrase and could no longer be verified or restored. A copy that cannot be
+// re-keyed is left in place on its old key and reported, never deleted.
+fn rekey_copies(path: &str, previous: Option<&str>, passphrase: Option<&str>) -> RekeyReport {
+    let mut files = corrupt_copies(path);
+    match backup::list_backups() {
+        Ok(backups) => files.extend(backups.into_iter().map(|backup| backup.path)),
+        Err(e) => error!("[Encryption] Failed to list backups: {:?}", e),
+    }
+    let mut report = RekeyReport::default();
+    for file in files {
+        match rekey_copy(&file, previous, passphrase) {
+            Ok(()) => report.rekeyed.push(file),
+            Err(e) => {
+                error!("[Encryption] Failed to re-key {}: {:?}", file, e);
+                report.failed.push(file);
+            }
+        }
+    }
+    report
+}
+fn reencrypt(passphrase: Option<&str>) -> AppResult<RekeyReport> {
+    let path = db::get_path();
+    let previous = db::passphrase();
+    reencrypt_live(&path, passphrase)?;
+    Ok(rekey_copies(&path, previous.as_deref(), passphrase))
+}
+pub fn enable(passphrase: &str) -> AppResult<RekeyReport> {
+    validate(passphrase)?;
+    if status().enabled {
+        return Err(AppError::Validation(
+            "Encryption is already enabled".to_string(),
+        ));
+    }
+    // Marked first: an encrypted file without the mark would look damaged at startup.
+    let path = db::get_path();
+    location::mark_encrypted(&path, true)?;
+    let report = reencrypt(Some(passphrase)).inspect_err(|_| {
+        if let Err(e) = location::mark_encrypted(&path, false) {
+            error!("[Encryption] Failed to clear the encryption mark: {:?}", e);
+        }
+    })?;
+    info!("[Encryption] Encryption enabled");
+    Ok(report)
+}
+pub fn change_passphrase(current: &str, new: &str) -> AppResult<RekeyReport> {
+    check_current(current)?;
+    validate(new)?;
+    let report = reencrypt(Some(new))?;
+    info!("[Encryption] Passphrase changed");
+    Ok(report)
+}
+pub fn disable(current: &str) -> AppResult<RekeyReport> {
+    check_current(current)?;
+    let report = reencrypt(None)?;
+    if let Err(e) = location::mark_encrypted(&db::get_path(), false) {
+        error!("[Encryption] Failed to clear the encryption mark: {:?}", e);
+    }
+    info!("[Encryption] Encryption disabled");
+    Ok(report)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn enabling_encryption_rekeys_backups() {
+        let dir = std::env::temp_dir().join(format!("paste_sheets_rekey_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_var("PASTE_SHEETS_DATA_DIR", &dir);
+        maintenance::startup_check().unwrap();
+        let clipboard = db::with_conn(|conn| db::clipboard_directory_id(conn)).unwrap();
+        db::post_content("before encryption", clipboard, None).unwrap();
+        let plaintext = backup::create_backup().unwrap();
+        assert!(!is_encrypted(&plaintext.path));
+        let report = enable("passphrase").unwrap();
+        assert_eq!(report.rekeyed, vec![plaintext.path.clone()]);
+        assert!(report.failed.is_empty());
+        let backups = backup::list_backups().unwrap();
+        assert_eq!(backups.len(), 1);
+        assert!(is_encrypted(&backups[0].path));
+        backup::verify_backup(Path::new(&backups[0].path)).unwrap();
+        db::post_content("after encryption", clipboard, None).unwrap();
+        backup::restore_backup(&backups[0].name).unwrap();
+        let contents: Vec<String> = db::get_all_contents()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.content)
+            .collect();
+        assert_eq!(contents, vec!["before encryption".to_string()]);
+        for backup in backup::list_backups().unwrap() {
+            assert!(is_encrypted(&backup.path));
+        }
+        location::mark_encrypted(&db::get_path(), false).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,72 @@
+use rusqlite::ffi::{SQLITE_CONSTRAINT_PRIMARYKEY, SQLITE_CONSTRAINT_UNIQUE};
+use rusqlite::ErrorCode;
+use std::fmt;
+// Serialized as `{ "code": "not_found", "message": "..." }`; the codes are part of the
+// command API, so rename variants only together with the frontend.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum AppError {
+    NotFound(String),
+    Duplicate(String),
+    ReservedName(String),
+    Validation(String),
+    Storage(String),
+    Clipboard(String),
+    Paste(String),
+}
+pub type AppResult<T> = Result<T, AppError>;
+impl AppError {
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Duplicate(message)
+            | AppError::ReservedName(message)
+            | AppError::Validation(message)
+            | AppError::Storage(message)
+            | AppError::Clipboard(message)
+            | AppError::Paste(message) => message,
+        }
+    }
+    pub fn not_found(what: &str) -> AppError {
+        AppError::NotFound(format!("{} not found", what))
+    }
+    pub fn reserved(name: &str) -> AppError {
+        AppError::ReservedName(format!("'{}' is reserved and cannot be changed", name))
+    }
+    pub fn duplicate_content() -> AppError {
+        AppError::Duplicate("The directory already contains this content".to_string())
+    }
+}
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+impl std::error::Error for AppError {}
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        match &error {
+            rusqlite::Error::QueryReturnedNoRows => AppError::not_found("Record"),
+            rusqlite::Error::SqliteFailure(e, _) if e.code == ErrorCode::ConstraintViolation => {
+                let duplicate = e.extended_code == SQLITE_CONSTRAINT_UNIQUE
+                    || e.extended_code == SQLITE_CONSTRAINT_PRIMARYKEY;
+                if duplicate {
+                    AppError::Duplicate(error.to_string())
+                } else {
+                    AppError::Validation(error.to_string())
+                }
+            }
+            _ => AppError::Storage(error.to_string()),
+        }
+    }
+}
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        AppError::Storage(error.to_string())
+    }
+}
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Validation(error.to_string())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::modules::db::{self, DirectoryInfo, PasteItem};
+use crate::modules::error::{AppError, AppResult};
+use std::fs;
+pub const EXPORT_FORMAT: &str = "pastesheets";
+pub const EXPORT_VERSION: u32 = 1;
+pub const CSV_HEADER: [&str; 8] = [
+    "directory",
+    "parent",
+    "content",
+    "memo",
+    "created_at",
+    "updated_at",
+    "pinned",
+    "tags",
+];
+pub const CSV_TAG_SEPARATOR: char = ';';
+pub const CSV_PATH_SEPARATOR: char = '/';
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ExportDocument {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub directories: Vec<ExportedDirectory>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ExportedDirectory {
+    pub name: String,
+    // Ancestor names from the top level down, since names only repeat across parents.
+    #[serde(default)]
+    pub parent: Vec<String>,
+    pub items: Vec<ExportedItem>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ExportedItem {
+    pub content: String,
+    pub memo: Option<String>,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ExportSummary {
+    pub directories: usize,
+    pub items: usize,
+}
+impl From<PasteItem> for ExportedItem {
+    fn from(item: PasteItem) -> Self {
+        ExportedItem {
+            content: item.content,
+            memo: item.memo,
+            created_at: item.created_at,
+            updated_at: Some(item.updated_at),
+            pinned: item.pinned,
+            tags: item.tags,
+        }
+    }
+}
+pub fn build_document(directories: Option<&[i64]>) -> AppResult<ExportDocument> {
+    let all = db::get_directories()?;
+    if let Some(ids) = directories {
+        if let Some(missing) = ids.iter().find(|id| !all.iter().any(|d| d.id == **id)) {
+            log::warn!("[Export] Unknown directory: {}", missing);
+            return Err(AppError::not_found(&format!("Directory {}", missing)));
+        }
+    }
+    let parent_path = |info: &DirectoryInfo| {
+        let mut path = Vec::new();
+        let mut parent_id = info.parent_id;
+        while let Some(parent) = parent_id.and_then(|id| all.iter().find(|d| d.id == id)) {
+            path.insert(0, parent.name.clone());
+            parent_id = parent.parent_id;
+        }
+        path
+    };
+    let mut exported = Vec::new();
+    for info in &all {
+        if directories.is_some_and(|ids| !ids.contains(&info.id)) {
+            continue;
+        }
+        let items = db::get_directory_contents(info.id, None)?;
+        exported.push(ExportedDirectory {
+            name: info.name.clone(),
+            parent: parent_path(info),
+            items: items.into_iter().map(ExportedItem::from).collect(),
+        });
+    }
+    let exported_at =
+        db::with_conn(|conn| conn.query_row("SELECT CURRENT_TIMESTAMP", [], |row| row.get(0)))?;
+    Ok(ExportDocument {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at,
+        directories: exported,
+    })
+}
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+pub fn to_csv(document: &ExportDocument) -> String {
+    let mut out = CSV_HEADER.join(",");
+    out.push_str("\r\n");
+    for directory in &document.directories {
+        for item in &directory.items {
+            let fields = [
+                csv_field(&directory.name),
+                csv_field(&directory.parent.join(&CSV_PATH_SEPARATOR.to_string())),
+                csv_field(&item.content),
+                csv_field(item.memo.as_deref().unwrap_or("")),
+                csv_field(&item.created_at),
+                csv_field(item.updated_at.as_deref().unwrap_or("")),
+                item.pinned.to_string(),
+                csv_field(&item.tags.join(&CSV_TAG_SEPARATOR.to_string())),
+            ];
+            out.push_str(&fields.join(","));
+            out.push_str("\r\n");
+        }
+    }
+    out
+}
+// A fence must be longer than any backtick run inside the content it wraps.
+fn code_fence(content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+pub fn to_markdown(document: &ExportDocument) -> String {
+    let mut out = format!(
+        "# PasteSheets export\n\nExported {}\n",
+        document.exported_at
+    );
+    for directory in &document.directories {
+        out.push_str(&format!("\n## {}\n", directory.name));
+        if !directory.parent.is_empty() {
+            out.push_str(&format!("\nInside {}\n", directory.parent.join(" / ")));
+        }
+        if directory.items.is_empty() {
+            out.push_str("\n_No items_\n");
+        }
+        for (index, item) in directory.items.iter().enumerate() {
+            let title = item
+                .memo
+                .as_deref()
+                .and_then(|memo| memo.lines().map(str::trim).find(|line| !line.is_empty()))
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Item {}", index + 1));
+            out.push_str(&format!("\n### {}\n\n", title));
+            let mut details = vec![format!("Created {}", item.created_at)];
+            if let Some(updated_at) = &item.updated_at {
+                details.push(format!("updated {}", updated_at));
+            }
+            if item.pinned {
+                details.push("pinned".to_string());
+            }
+            if !item.tags.is_empty() {
+                details.push(format!("tags: {}", item.tags.join(", ")));
+            }
+            out.push_str(&format!("_{}_\n\n", details.join(" · ")));
+            let fence = code_fence(&item.content);
+            out.push_str(&format!("{}\n{}\n{}\n", fence, item.content, fence));
+        }
+    }
+    out
+}
+pub fn export_directories(
+    path: &str,
+    format: ExportFormat,
+    directories: Option<&[i64]>,
+) -> AppResult<ExportSummary> {
+    let document = build_document(directories)?;
+    let contents = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&document)?,
+        ExportFormat::Csv => to_csv(&document),
+        ExportFormat::Markdown => to_markdown(&document),
+    };
+    fs::write(path, contents)?;
+    let summary = ExportSummary {
+        directories: document.directories.len(),
+        items: document.directories.iter().map(|d| d.items.len()).sum(),
+    };
+    log::info!(
+        "[Export] Wrote {} items from {} directories to {}",
+        summary.items,
+        summary.directories,
+        path
+    );
+    Ok(summary)
+}
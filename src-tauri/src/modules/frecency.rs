@@ -0,0 +1,77 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+pub const HALF_LIFE_DAYS: f64 = 7.0;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+// Frecency is stored as the unix time at which the decayed score would fall to 1.
+// Every item decays at the same rate, so ordering by the stored value is the same
+// as ordering by the current score and rows never need to be rewritten as time passes.
+fn decay_rate() -> f64 {
+    std::f64::consts::LN_2 / (HALF_LIFE_DAYS * SECONDS_PER_DAY)
+}
+pub fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+pub fn bump(previous: Option<f64>, used_at: f64) -> f64 {
+    match previous {
+        None => used_at,
+        Some(previous) => {
+            let (high, low) = if previous > used_at {
+                (previous, used_at)
+            } else {
+                (used_at, previous)
+            };
+            high + (-(high - low) * decay_rate()).exp().ln_1p() / decay_rate()
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn score(frecency: f64, at: f64) -> f64 {
+        ((frecency - at) * decay_rate()).exp()
+    }
+    const DAY: f64 = SECONDS_PER_DAY;
+    const START: f64 = 1_767_225_600.0;
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+    #[test]
+    fn single_use_scores_one_at_use_time() {
+        assert_close(score(bump(None, START), START), 1.0);
+    }
+    #[test]
+    fn score_halves_every_half_life() {
+        let frecency = bump(None, START);
+        assert_close(score(frecency, START + HALF_LIFE_DAYS * DAY), 0.5);
+        assert_close(score(frecency, START + 2.0 * HALF_LIFE_DAYS * DAY), 0.25);
+    }
+    #[test]
+    fn uses_accumulate_with_decay() {
+        let frecency = bump(Some(bump(None, START)), START + HALF_LIFE_DAYS * DAY);
+        assert_close(score(frecency, START + HALF_LIFE_DAYS * DAY), 1.5);
+        let same_time = bump(Some(bump(None, START)), START);
+        assert_close(score(same_time, START), 2.0);
+    }
+    #[test]
+    fn bump_order_does_not_matter() {
+        let a = bump(Some(bump(None, START)), START + 3.0 * DAY);
+        let b = bump(Some(bump(None, START + 3.0 * DAY)), START);
+        assert_close(score(a, START + 5.0 * DAY), score(b, START + 5.0 * DAY));
+    }
+    #[test]
+    fn frequent_use_outranks_single_recent_use() {
+        let daily = (0..7).fold(None, |acc, day| Some(bump(acc, START + day as f64 * DAY)));
+        let fresh = bump(None, START + 7.0 * DAY);
+        assert!(daily.unwrap() > fresh);
+        let stale = (0..7).fold(None, |acc, day| Some(bump(acc, START + day as f64 * DAY)));
+        let much_later = bump(None, START + 60.0 * DAY);
+        assert!(stale.unwrap() < much_later);
+    }
+}
@@ -0,0 +1,441 @@
+use crate::modules::db;
+use crate::modules::error::{AppError, AppResult};
+use crate::modules::export::{
+    ExportDocument, CSV_PATH_SEPARATOR, CSV_TAG_SEPARATOR, EXPORT_FORMAT, EXPORT_VERSION,
+};
+use crate::modules::retention;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Result, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+const DEFAULT_DIRECTORY: &str = "Imported";
+// Core Data stores dates as seconds since 2001-01-01 UTC.
+const CORE_DATA_EPOCH_OFFSET: i64 = 978_307_200;
+const MACCY_TEXT_TYPE: &str = "public.utf8-plain-text";
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    Json,
+    Csv,
+    Maccy,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ImportIssue {
+    pub row: usize,
+    pub directory: Option<String>,
+    pub reason: String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub created_directories: Vec<String>,
+    pub duplicates: Vec<ImportIssue>,
+    pub skipped: Vec<ImportIssue>,
+}
+struct ImportRow {
+    row: usize,
+    directory: Option<String>,
+    parent: Vec<String>,
+    content: String,
+    memo: Option<String>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    pinned: bool,
+    tags: Vec<String>,
+}
+#[derive(Default)]
+struct ParsedImport {
+    parents: Vec<(String, Vec<String>)>,
+    rows: Vec<ImportRow>,
+    skipped: Vec<ImportIssue>,
+}
+fn parse_json(text: &str) -> AppResult<ParsedImport> {
+    let document: ExportDocument = serde_json::from_str(text)?;
+    if document.format != EXPORT_FORMAT || document.version > EXPORT_VERSION {
+        log::warn!(
+            "[Import] Unsupported export: {} v{}",
+            document.format,
+            document.version
+        );
+        return Err(AppError::Validation(format!(
+            "Unsupported export: {} v{}",
+            document.format, document.version
+        )));
+    }
+    let mut parsed = ParsedImport::default();
+    for directory in document.directories {
+        parsed
+            .parents
+            .push((directory.name.clone(), directory.parent.clone()));
+        for item in directory.items {
+            let row = parsed.rows.len() + parsed.skipped.len() + 1;
+            parsed.rows.push(ImportRow {
+                row,
+                directory: Some(directory.name.clone()),
+                parent: directory.parent.clone(),
+                content: item.content,
+                memo: item.memo,
+                created_at: Some(item.created_at),
+                updated_at: item.updated_at,
+                pinned: item.pinned,
+                tags: item.tags,
+            });
+        }
+    }
+    Ok(parsed)
+}
+fn parse_csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) | ('\r', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+fn parse_csv(text: &str) -> AppResult<ParsedImport> {
+    let mut records = parse_csv_records(text).into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or_else(|| AppError::Validation("CSV file is empty".to_string()))?
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let content_column = column("content")
+        .ok_or_else(|| AppError::Validation("CSV header has no content column".to_string()))?;
+    let directory_column = column("directory");
+    let parent_column = column("parent");
+    let memo_column = column("memo");
+    let created_column = column("created_at");
+    let updated_column = column("updated_at");
+    let pinned_column = column("pinned");
+    let tags_column = column("tags");
+    let mut parsed = ParsedImport::default();
+    for (index, record) in records.enumerate() {
+        let row = index + 1;
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+        if record.len() != header.len() {
+            parsed.skipped.push(ImportIssue {
+                row,
+                directory: None,
+                reason: format!("expected {} fields, found {}", header.len(), record.len()),
+            });
+            continue;
+        }
+        let field = |column: Option<usize>| {
+            column
+                .map(|i| record[i].clone())
+                .filter(|value| !value.trim().is_empty())
+        };
+        parsed.rows.push(ImportRow {
+            row,
+            directory: field(directory_column).map(|name| name.trim().to_string()),
+            parent: field(parent_column)
+                .map(|path| {
+                    path.split(CSV_PATH_SEPARATOR)
+                        .map(|name| name.trim().to_string())
+                        .filter(|name| !name.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            content: record[content_column].clone(),
+            memo: field(memo_column),
+            created_at: field(created_column),
+            updated_at: field(updated_column),
+            pinned: field(pinned_column).is_some_and(|value| {
+                matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes")
+            }),
+            tags: field(tags_column)
+                .map(|tags| {
+                    tags.split(CSV_TAG_SEPARATOR)
+                        .map(|tag| tag.trim().to_string())
+                        .filter(|tag| !tag.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        });
+    }
+    Ok(parsed)
+}
+fn parse_maccy(path: &str) -> AppResult<ParsedImport> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = conn.prepare(
+        "SELECT datetime(i.ZFIRSTCOPIEDAT + ?1, 'unixepoch'),
+            datetime(i.ZLASTCOPIEDAT + ?1, 'unixepoch'),
+            i.ZPIN IS NOT NULL,
+            c.ZVALUE
+         FROM ZHISTORYITEM i
+         JOIN ZHISTORYITEMCONTENT c ON c.ZITEM = i.Z_PK AND c.ZTYPE = ?2
+         ORDER BY i.ZFIRSTCOPIEDAT, i.Z_PK",
+    )?;
+    let rows = stmt.query_map(
+        rusqlite::params![CORE_DATA_EPOCH_OFFSET, MACCY_TEXT_TYPE],
+        |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, bool>(2)?,
+                row.get::<_, Option<Vec<u8>>>(3)?,
+            ))
+        },
+    )?;
+    let mut parsed = ParsedImport::default();
+    for (index, row) in rows.enumerate() {
+        let (created_at, updated_at, pinned, value) = row?;
+        match value.map(String::from_utf8) {
+            Some(Ok(content)) => parsed.rows.push(ImportRow {
+                row: index + 1,
+                directory: None,
+                parent: Vec::new(),
+                content,
+                memo: None,
+                created_at,
+                updated_at,
+                pinned,
+                tags: Vec::new(),
+            }),
+            _ => parsed.skipped.push(ImportIssue {
+                row: index + 1,
+                directory: None,
+                reason: "content is not UTF-8 text".to_string(),
+            }),
+        }
+    }
+    Ok(parsed)
+}
+fn ensure_child(
+    tx: &Transaction,
+    parent_id: Option<i64>,
+    name: &str,
+    created: &mut Vec<String>,
+) -> Result<i64> {
+    let existing: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM directories
+             WHERE name = ?1 AND parent_id IS ?2 AND deleted_at IS NULL",
+            rusqlite::params![name, parent_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match existing {
+        Some(id) => Ok(id),
+        None => {
+            tx.execute(
+                "INSERT INTO directories (name, parent_id) VALUES (?1, ?2)",
+                rusqlite::params![name, parent_id],
+            )?;
+            created.push(name.to_string());
+            Ok(tx.last_insert_rowid())
+        }
+    }
+}
+// Names are only unique among siblings, so each level is looked up under the one above it.
+fn ensure_directory(
+    tx: &Transaction,
+    name: &str,
+    parent: &[String],
+    created: &mut Vec<String>,
+) -> Result<i64> {
+    let mut parent_id = None;
+    for ancestor in parent.iter().filter(|ancestor| *ancestor != "Clipboard") {
+        parent_id = Some(ensure_child(tx, parent_id, ancestor, created)?);
+    }
+    ensure_child(tx, parent_id, name, created)
+}
+fn insert_rows(parsed: ParsedImport, default_directory: &str) -> AppResult<ImportSummary> {
+    let ParsedImport {
+        parents,
+        rows,
+        mut skipped,
+    } = parsed;
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let mut created_directories = Vec::new();
+        let mut directory_ids: HashMap<Vec<String>, i64> = HashMap::new();
+        for (name, parent) in &parents {
+            let id = ensure_directory(&tx, name, parent, &mut created_directories)?;
+            directory_ids.insert([parent.as_slice(), std::slice::from_ref(name)].concat(), id);
+        }
+        let mut seen: HashSet<(i64, String)> = HashSet::new();
+        let mut duplicates = Vec::new();
+        let mut imported = 0;
+        for row in rows {
+            let name = row
+                .directory
+                .clone()
+                .unwrap_or_else(|| default_directory.to_string());
+            let path = [row.parent.as_slice(), std::slice::from_ref(&name)].concat();
+            if row.content.trim().is_empty() {
+                skipped.push(ImportIssue {
+                    row: row.row,
+                    directory: Some(name),
+                    reason: "empty content".to_string(),
+                });
+                continue;
+            }
+            let directory_id = match directory_ids.get(&path) {
+                Some(id) => *id,
+                None => {
+                    let id = ensure_directory(&tx, &name, &row.parent, &mut created_directories)?;
+                    directory_ids.insert(path, id);
+                    id
+                }
+            };
+            let content_hash = db::content_hash(&row.content);
+            let exists: bool = tx
+                .prepare_cached(
+                    "SELECT EXISTS (
+                        SELECT 1 FROM paste_sheets
+                        WHERE directory_id = ?1 AND content_hash = ?2 AND content = ?3
+                          AND deleted_at IS NULL
+                     )",
+                )?
+                .query_row(
+                    rusqlite::params![directory_id, content_hash, row.content],
+                    |r| r.get(0),
+                )?;
+            if exists || !seen.insert((directory_id, row.content.clone())) {
+                duplicates.push(ImportIssue {
+                    row: row.row,
+                    directory: Some(name),
+                    reason: "content already exists in directory".to_string(),
+                });
+                continue;
+            }
+            tx.prepare_cached(
+                "INSERT INTO paste_sheets
+                    (content, directory_id, memo, created_at, updated_at, pinned, position,
+                     content_hash)
+                 VALUES (?1, ?2, ?3,
+                    COALESCE(datetime(?4), CURRENT_TIMESTAMP), datetime(?5), ?6,
+                    (SELECT COALESCE(MAX(position), 0) + 1 FROM paste_sheets
+                     WHERE directory_id = ?2), ?7)",
+            )?
+            .execute(rusqlite::params![
+                row.content,
+                directory_id,
+                row.memo,
+                row.created_at,
+                row.updated_at,
+                row.pinned,
+                content_hash
+            ])?;
+            let item_id = tx.last_insert_rowid();
+            for tag in &row.tags {
+                tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [tag])?;
+                tx.execute(
+                    "INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+                     SELECT ?1, id FROM tags WHERE name = ?2",
+                    rusqlite::params![item_id, tag],
+                )?;
+            }
+            imported += 1;
+        }
+        for directory_id in directory_ids.values() {
+            retention::enforce(&tx, *directory_id)?;
+        }
+        tx.commit()?;
+        Ok(ImportSummary {
+            imported,
+            created_directories,
+            duplicates,
+            skipped,
+        })
+    })
+}
+pub fn import_file(
+    path: &str,
+    format: ImportFormat,
+    directory: Option<&str>,
+) -> AppResult<ImportSummary> {
+    let parsed = match format {
+        ImportFormat::Json => parse_json(&fs::read_to_string(path)?)?,
+        ImportFormat::Csv => parse_csv(&fs::read_to_string(path)?)?,
+        ImportFormat::Maccy => parse_maccy(path)?,
+    };
+    let default_directory = directory
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_DIRECTORY);
+    let summary = insert_rows(parsed, default_directory)?;
+    log::info!(
+        "[Import] {} imported, {} duplicates, {} skipped from {}",
+        summary.imported,
+        summary.duplicates.len(),
+        summary.skipped.len(),
+        path
+    );
+    Ok(summary)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn records(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|field| field.to_string()).collect())
+            .collect()
+    }
+    #[test]
+    fn quoted_fields_keep_commas_newlines_and_quotes() {
+        let text =
+            "directory,content\nWork,\"a, b\"\nWork,\"line 1\nline 2\"\nWork,\"say \"\"hi\"\"\"\n";
+        assert_eq!(
+            parse_csv_records(text),
+            records(&[
+                &["directory", "content"],
+                &["Work", "a, b"],
+                &["Work", "line 1\nline 2"],
+                &["Work", "say \"hi\""],
+            ])
+        );
+    }
+    #[test]
+    fn crlf_line_endings_end_records_but_stay_inside_quotes() {
+        let text = "content,memo\r\nfirst,\"one\r\ntwo\"\r\nsecond,\r\n";
+        assert_eq!(
+            parse_csv_records(text),
+            records(&[
+                &["content", "memo"],
+                &["first", "one\r\ntwo"],
+                &["second", ""],
+            ])
+        );
+    }
+    #[test]
+    fn byte_order_mark_is_not_part_of_the_header() {
+        let text = "\u{feff}content,tags\nhello,a;b";
+        assert_eq!(
+            parse_csv_records(text),
+            records(&[&["content", "tags"], &["hello", "a;b"]])
+        );
+    }
+    #[test]
+    fn empty_quoted_fields_and_missing_final_newline() {
+        assert_eq!(
+            parse_csv_records("\"\",x,\n\"\"\"\""),
+            records(&[&["", "x", ""], &["\""]])
+        );
+    }
+}
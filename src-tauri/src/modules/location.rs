@@ -0,0 +1,243 @@
+use crate::modules::error::{AppError, AppResult};
+use crate::modules::{backup, db};
+use log::{error, info, warn};
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+const DATABASE_FILE: &str = "paste_sheets.db";
+const DATA_DIR_ENV: &str = "PASTE_SHEETS_DATA_DIR";
+const DATA_DIR_FLAG: &str = "--data-dir";
+const PORTABLE_FLAG: &str = "--portable";
+const PORTABLE_MARKER: &str = "paste_sheets.portable";
+const CONFIG_DIRECTORY: &str = "paste_sheets";
+const CONFIG_FILE: &str = "location.json";
+static LOCATION: Mutex<Option<DataLocation>> = Mutex::new(None);
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationSource {
+    CommandLine,
+    Environment,
+    Portable,
+    Setting,
+    Default,
+}
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct DataLocation {
+    pub data_dir: String,
+    pub database_path: String,
+    pub source: LocationSource,
+}
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct LocationConfig {
+    data_dir: Option<String>,
+    // Databases the user encrypted. A header SQLite cannot read only means "locked" for
+    // these; anywhere else it means damage.
+    #[serde(default)]
+    encrypted: Vec<String>,
+}
+fn flag_value(args: &[String]) -> Option<String> {
+    let prefix = format!("{}=", DATA_DIR_FLAG);
+    args.iter().enumerate().find_map(|(index, arg)| {
+        if arg == DATA_DIR_FLAG {
+            args.get(index + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(String::from)
+        }
+    })
+}
+fn executable_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIRECTORY).join(CONFIG_FILE))
+}
+fn load_config() -> LocationConfig {
+    config_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+fn save_config(config: &LocationConfig) -> AppResult<()> {
+    let path = config_path()
+        .ok_or_else(|| AppError::Storage("No configuration directory is available".to_string()))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = serde_json::to_string_pretty(config)?;
+    fs::write(path, text)?;
+    Ok(())
+}
+pub fn is_marked_encrypted(database_path: &str) -> bool {
+    load_config()
+        .encrypted
+        .iter()
+        .any(|path| path == database_path)
+}
+pub fn mark_encrypted(database_path: &str, encrypted: bool) -> AppResult<()> {
+    let mut config = load_config();
+    config.encrypted.retain(|path| path != database_path);
+    if encrypted {
+        config.encrypted.push(database_path.to_string());
+    }
+    save_config(&config)
+}
+fn default_dir() -> PathBuf {
+    dirs::data_dir()
+        .or_else(dirs::home_dir)
+        .or_else(executable_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+fn non_empty(value: Option<String>) -> Option<PathBuf> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+// Explicit overrides win over portable mode, which wins over the saved location.
+fn resolve() -> (PathBuf, LocationSource) {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(dir) = non_empty(flag_value(&args)) {
+        return (dir, LocationSource::CommandLine);
+    }
+    if let Some(dir) = non_empty(std::env::var(DATA_DIR_ENV).ok()) {
+        return (dir, LocationSource::Environment);
+    }
+    if let Some(exe_dir) = executable_dir() {
+        if args.iter().any(|arg| arg == PORTABLE_FLAG) || exe_dir.join(PORTABLE_MARKER).exists() {
+            return (exe_dir, LocationSource::Portable);
+        }
+    }
+    if let Some(dir) = non_empty(load_config().data_dir) {
+        return (dir, LocationSource::Setting);
+    }
+    (default_dir(), LocationSource::Default)
+}
+fn location_for(data_dir: PathBuf, source: LocationSource) -> DataLocation {
+    if let Err(e) = fs::create_dir_all(&data_dir) {
+        error!("[Location] Failed to create {:?}: {:?}", data_dir, e);
+    }
+    DataLocation {
+        database_path: data_dir.join(DATABASE_FILE).to_string_lossy().to_string(),
+        data_dir: data_dir.to_string_lossy().to_string(),
+        source,
+    }
+}
+pub fn current() -> DataLocation {
+    let mut location = LOCATION
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    location
+        .get_or_insert_with(|| {
+            let (data_dir, source) = resolve();
+            location_for(data_dir, source)
+        })
+        .clone()
+}
+fn remove_database_files(path: &str) {
+    for suffix in ["", "-wal", "-shm"] {
+        let file = format!("{}{}", path, suffix);
+        if Path::new(&file).exists() {
+            if let Err(e) = fs::remove_file(&file) {
+                warn!("[Location] Failed to remove {}: {:?}", file, e);
+            }
+        }
+    }
+}
+fn move_backups(from: &Path, to: &Path) {
+    let Ok(entries) = fs::read_dir(from) else {
+        return;
+    };
+    if let Err(e) = fs::create_dir_all(to) {
+        warn!("[Location] Failed to create {:?}: {:?}", to, e);
+        return;
+    }
+    for entry in entries.flatten() {
+        let target = to.join(entry.file_name());
+        let moved = fs::rename(entry.path(), &target).or_else(|_| {
+            fs::copy(entry.path(), &target).and_then(|_| fs::remove_file(entry.path()))
+        });
+        if let Err(e) = moved {
+            warn!(
+                "[Location] Failed to move backup {:?}: {:?}",
+                entry.path(),
+                e
+            );
+        }
+    }
+    let _ = fs::remove_dir(from);
+}
+pub fn move_database(destination: &str) -> AppResult<DataLocation> {
+    let previous = current();
+    if !matches!(
+        previous.source,
+        LocationSource::Setting | LocationSource::Default
+    ) {
+        return Err(AppError::Validation(
+            "The data location is fixed by a command-line flag, environment variable or portable mode"
+                .to_string(),
+        ));
+    }
+    let target_dir = non_empty(Some(destination.to_string()))
+        .ok_or_else(|| AppError::Validation("Destination cannot be empty".to_string()))?;
+    fs::create_dir_all(&target_dir)?;
+    let target_dir = target_dir.canonicalize()?;
+    let previous_dir = Path::new(&previous.data_dir).canonicalize()?;
+    let target = location_for(target_dir.clone(), LocationSource::Setting);
+    if target_dir == previous_dir {
+        return Err(AppError::Validation(
+            "The database is already stored there".to_string(),
+        ));
+    }
+    if Path::new(&target.database_path).exists() {
+        return Err(AppError::Duplicate(format!(
+            "A database already exists at {}",
+            target.database_path
+        )));
+    }
+    let previous_backups = backup::backup_dir();
+    // The copy is opened before anything points at it, so a failure leaves the app, the
+    // saved location and the original database exactly as they were.
+    db::with_conn(|conn| -> AppResult<()> {
+        let copied = Connection::open(&target.database_path)
+            .and_then(|mut dst| {
+                db::apply_key(&dst)?;
+                backup::copy_database(conn, &mut dst)
+            })
+            .and_then(|_| db::open_connection(&target.database_path));
+        let mut config = load_config();
+        config.data_dir = Some(target.data_dir.clone());
+        for path in config.encrypted.iter_mut() {
+            if *path == previous.database_path {
+                *path = target.database_path.clone();
+            }
+        }
+        let opened = copied
+            .map_err(AppError::from)
+            .and_then(|fresh| save_config(&config).map(|_| fresh));
+        let fresh = match opened {
+            Ok(fresh) => fresh,
+            Err(e) => {
+                remove_database_files(&target.database_path);
+                return Err(e);
+            }
+        };
+        let old = std::mem::replace(conn, fresh);
+        if let Err((_, e)) = old.close() {
+            warn!("[Location] Failed to close previous database: {:?}", e);
+        }
+        *LOCATION
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(target.clone());
+        remove_database_files(&previous.database_path);
+        Ok(())
+    })?;
+    move_backups(&previous_backups, &backup::backup_dir());
+    info!(
+        "[Location] Moved database from {} to {}",
+        previous.database_path, target.database_path
+    );
+    Ok(target)
+}
@@ -0,0 +1,416 @@
+use crate::modules::error::AppResult;
+use crate::modules::{backup, db, encryption, migrations};
+use log::{error, info, warn};
+use rusqlite::types::Value;
+use rusqlite::{Connection, ErrorCode, OpenFlags, Result};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+// Parents before children so salvaged rows keep their references.
+const SALVAGE_TABLES: &[&str] = &[
+    "directories",
+    "settings",
+    "tags",
+    "paste_sheets",
+    "item_tags",
+    "item_revisions",
+];
+const CLEANUP_ORPHANS: &str = "
+    UPDATE directories SET parent_id = NULL
+    WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM directories);
+    UPDATE paste_sheets SET directory_id = (SELECT id FROM directories WHERE name = 'Clipboard')
+    WHERE directory_id NOT IN (SELECT id FROM directories);
+    DELETE FROM item_tags
+    WHERE item_id NOT IN (SELECT id FROM paste_sheets) OR tag_id NOT IN (SELECT id FROM tags);
+    DELETE FROM item_revisions WHERE item_id NOT IN (SELECT id FROM paste_sheets);
+    INSERT INTO paste_sheets_fts(paste_sheets_fts) VALUES ('rebuild');";
+static LAST_RECOVERY: Mutex<Option<RecoveryReport>> = Mutex::new(None);
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SalvagedTable {
+    pub table: String,
+    pub rows: usize,
+    pub failed: usize,
+}
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecoveryReport {
+    pub moved_to: String,
+    pub restored_backup: Option<String>,
+    pub salvaged: Vec<SalvagedTable>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DiagnosticReport {
+    pub database_path: String,
+    pub size_bytes: u64,
+    pub wal_size_bytes: u64,
+    pub page_size: i64,
+    pub page_count: i64,
+    pub freelist_count: i64,
+    pub journal_mode: String,
+    pub schema_version: i64,
+    pub latest_schema_version: i64,
+    pub encrypted: bool,
+    pub directory_count: i64,
+    pub item_count: i64,
+    pub trashed_item_count: i64,
+    pub backup_count: usize,
+    pub latest_backup: Option<String>,
+    pub quick_check: Vec<String>,
+    pub last_recovery: Option<RecoveryReport>,
+}
+fn file_size(path: &str) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+fn run_check(conn: &Connection, pragma: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA {}", pragma))?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let problems: Vec<String> = rows.collect::<Result<_>>()?;
+    Ok(problems.into_iter().filter(|row| row != "ok").collect())
+}
+pub fn quick_check() -> AppResult<Vec<String>> {
+    Ok(db::with_conn(|conn| run_check(conn, "quick_check"))?)
+}
+pub fn integrity_check() -> AppResult<Vec<String>> {
+    Ok(db::with_conn(|conn| run_check(conn, "integrity_check"))?)
+}
+pub fn vacuum() -> AppResult<u64> {
+    let path = db::get_path();
+    let before = file_size(&path) + file_size(&format!("{}-wal", path));
+    db::with_conn(|conn| conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);"))?;
+    let after = file_size(&path) + file_size(&format!("{}-wal", path));
+    info!(
+        "[Maintenance] Vacuum reclaimed {} bytes",
+        before.saturating_sub(after)
+    );
+    Ok(before.saturating_sub(after))
+}
+pub fn analyze() -> AppResult<()> {
+    Ok(db::with_conn(|conn| {
+        conn.execute_batch("ANALYZE; PRAGMA optimize;")
+    })?)
+}
+fn is_corruption(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::DatabaseCorrupt) | Some(ErrorCode::NotADatabase)
+    )
+}
+fn common_columns(src: &Connection, dst: &Connection, table: &str) -> Result<Vec<String>> {
+    let columns = |conn: &Connection| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+        let rows = stmt.query_map([table], |row| row.get(0))?;
+        rows.collect()
+    };
+    let available = columns(src)?;
+    Ok(columns(dst)?
+        .into_iter()
+        .filter(|column| available.contains(column))
+        .collect())
+}
+// Rows are copied one at a time so a damaged page only costs the rows behind it.
+fn salvage_table(src: &Connection, dst: &Connection, table: &str, conflict: &str) -> SalvagedTable {
+    let mut salvaged = SalvagedTable {
+        table: table.to_string(),
+        rows: 0,
+        failed: 0,
+    };
+    let columns = match common_columns(src, dst, table) {
+        Ok(columns) if !columns.is_empty() => columns.join(", "),
+        Ok(_) => return salvaged,
+        Err(e) => {
+            warn!("[Maintenance] Cannot read columns of {}: {:?}", table, e);
+            return salvaged;
+        }
+    };
+    let copied = (|| -> Result<()> {
+        let placeholders = vec!["?"; columns.split(", ").count()].join(", ");
+        let mut insert = dst.prepare(&format!(
+            "INSERT OR {} INTO {} ({}) VALUES ({})",
+            conflict, table, columns, placeholders
+        ))?;
+        let mut select = src.prepare(&format!("SELECT {} FROM {}", columns, table))?;
+        let width = select.column_count();
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let values = (0..width)
+                .map(|index| row.get::<_, Value>(index))
+                .collect::<Result<Vec<_>>>();
+            match values.and_then(|values| insert.execute(rusqlite::params_from_iter(values))) {
+                Ok(_) => salvaged.rows += 1,
+                Err(_) => salvaged.failed += 1,
+            }
+        }
+        Ok(())
+    })();
+    if let Err(e) = copied {
+        warn!("[Maintenance] Stopped salvaging {}: {:?}", table, e);
+    }
+    salvaged
+}
+fn open_source(path: &str) -> Result<Connection> {
+    let src = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    db::apply_key(&src)?;
+    Ok(src)
+}
+// Filling gaps never overwrites a row that is already there, including the default
+// settings; otherwise salvaged settings replace the defaults from the fresh schema.
+fn copy_rows(src: &Connection, dst: &Connection, fill_gaps: bool) -> Vec<SalvagedTable> {
+    SALVAGE_TABLES
+        .iter()
+        .map(|table| {
+            let conflict = if *table == "settings" && !fill_gaps {
+                "REPLACE"
+            } else {
+                "IGNORE"
+            };
+            salvage_table(src, dst, table, conflict)
+        })
+        .collect()
+}
+fn newest_good_backup() -> Option<(String, Connection)> {
+    let backups = backup::list_backups().unwrap_or_else(|e| {
+        warn!("[Maintenance] Cannot list backups: {:?}", e);
+        Vec::new()
+    });
+    backups.into_iter().find_map(|candidate| {
+        backup::verify_backup(Path::new(&candidate.path)).ok()?;
+        let src = open_source(&candidate.path).ok()?;
+        Some((candidate.name, src))
+    })
+}
+// The damaged file holds the newest rows, so it is salvaged first and the backup only
+// fills in the rows that could not be read.
+fn salvage(
+    damaged: Option<&Connection>,
+    backup: Option<&Connection>,
+    dst: &Connection,
+) -> Result<Vec<SalvagedTable>> {
+    dst.pragma_update(None, "foreign_keys", "OFF")?;
+    let salvaged = damaged
+        .map(|src| copy_rows(src, dst, false))
+        .unwrap_or_default();
+    if let Some(src) = backup {
+        copy_rows(src, dst, true);
+    }
+    let cleaned = dst
+        .execute_batch(CLEANUP_ORPHANS)
+        .and_then(|_| db::fill_content_hashes(dst));
+    dst.pragma_update(None, "foreign_keys", "ON")?;
+    cleaned?;
+    Ok(salvaged)
+}
+fn move_aside(path: &str) -> AppResult<String> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let moved_to = format!("{}.corrupt-{}", path, stamp);
+    for suffix in ["", "-wal", "-shm"] {
+        let from = format!("{}{}", path, suffix);
+        if Path::new(&from).exists() {
+            fs::rename(&from, format!("{}{}", moved_to, suffix))?;
+        }
+    }
+    Ok(moved_to)
+}
+fn move_back(path: &str, moved_to: &str) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", path, suffix));
+        let from = format!("{}{}", moved_to, suffix);
+        if Path::new(&from).exists() {
+            if let Err(e) = fs::rename(&from, format!("{}{}", path, suffix)) {
+                error!("[Maintenance] Failed to put back {}: {:?}", from, e);
+            }
+        }
+    }
+}
+fn open_replacement(path: &str) -> AppResult<Connection> {
+    let mut conn = db::open_connection(path)?;
+    migrations::run(&mut conn)?;
+    Ok(conn)
+}
+// A failed rebuild puts the damaged files back, so recovery can be retried later.
+fn rebuild(path: &str) -> AppResult<(Connection, RecoveryReport)> {
+    let moved_to = move_aside(path)?;
+    let conn = match open_replacement(path) {
+        Ok(conn) => conn,
+        Err(e) => {
+            move_back(path, &moved_to);
+            return Err(e);
+        }
+    };
+    let damaged = open_source(&moved_to)
+        .map_err(|e| warn!("[Maintenance] Cannot open {}: {:?}", moved_to, e))
+        .ok();
+    let backup = newest_good_backup();
+    let salvaged = salvage(damaged.as_ref(), backup.as_ref().map(|(_, src)| src), &conn)
+        .unwrap_or_else(|e| {
+            warn!(
+                "[Maintenance] Salvage of {} did not finish: {:?}",
+                moved_to, e
+            );
+            Vec::new()
+        });
+    let restored_backup = backup.map(|(name, _)| name);
+    Ok((
+        conn,
+        RecoveryReport {
+            moved_to,
+            restored_backup,
+            salvaged,
+        },
+    ))
+}
+// The damaged file may not even accept the connection pragmas, so fall back to a bare
+// keyed connection. Reads will keep failing, but the app stays on the real file.
+fn reopen_damaged(path: &str) -> Result<Connection> {
+    db::open_connection(path).or_else(|_| {
+        let conn = Connection::open(path)?;
+        db::apply_key(&conn)?;
+        Ok(conn)
+    })
+}
+pub fn recover() -> AppResult<RecoveryReport> {
+    let path = db::get_path();
+    let report = if db::is_initialized() {
+        db::with_conn(|conn| -> AppResult<RecoveryReport> {
+            let damaged = std::mem::replace(conn, Connection::open_in_memory()?);
+            if let Err((_, e)) = damaged.close() {
+                warn!("[Maintenance] Failed to close damaged database: {:?}", e);
+            }
+            match rebuild(&path) {
+                Ok((fresh, report)) => {
+                    *conn = fresh;
+                    Ok(report)
+                }
+                Err(e) => {
+                    error!("[Maintenance] Recovery failed: {:?}", e);
+                    match reopen_damaged(&path) {
+                        Ok(damaged) => *conn = damaged,
+                        Err(reopen) => {
+                            error!("[Maintenance] Failed to reopen {}: {:?}", path, reopen)
+                        }
+                    }
+                    Err(e)
+                }
+            }
+        })?
+    } else {
+        let (fresh, report) = rebuild(&path)?;
+        db::install_connection(fresh);
+        report
+    };
+    info!(
+        "[Maintenance] Recovered database, damaged file kept at {}",
+        report.moved_to
+    );
+    *LAST_RECOVERY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(report.clone());
+    Ok(report)
+}
+pub fn startup_check() -> AppResult<Option<RecoveryReport>> {
+    let checked = db::with_conn(|conn| {
+        migrations::run(conn)?;
+        db::fill_content_hashes(conn)?;
+        run_check(conn, "quick_check")
+    });
+    match checked {
+        Ok(problems) if problems.is_empty() => Ok(None),
+        Ok(problems) => {
+            error!("[Maintenance] Quick check failed: {:?}", problems);
+            recover().map(Some)
+        }
+        Err(e) if is_corruption(&e) => {
+            error!("[Maintenance] Database is unreadable: {:?}", e);
+            recover().map(Some)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+pub fn diagnostics() -> AppResult<DiagnosticReport> {
+    let path = db::get_path();
+    let backups = backup::list_backups()?;
+    let quick_check = quick_check()?;
+    db::with_conn(|conn| -> AppResult<DiagnosticReport> {
+        let pragma = |name: &str| conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0));
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get(0));
+        Ok(DiagnosticReport {
+            size_bytes: file_size(&path),
+            wal_size_bytes: file_size(&format!("{}-wal", path)),
+            page_size: pragma("page_size")?,
+            page_count: pragma("page_count")?,
+            freelist_count: pragma("freelist_count")?,
+            journal_mode: conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?,
+            schema_version: migrations::current_version(conn)?,
+            latest_schema_version: migrations::latest_version(),
+            encrypted: encryption::is_encrypted(&path),
+            directory_count: count("SELECT COUNT(*) FROM directories WHERE deleted_at IS NULL")?,
+            item_count: count(&format!("SELECT COUNT(*) FROM {}", db::ITEM_SOURCE))?,
+            trashed_item_count: count(
+                "SELECT COUNT(*) FROM paste_sheets WHERE deleted_at IS NOT NULL",
+            )?,
+            backup_count: backups.len(),
+            latest_backup: backups.first().map(|backup| backup.name.clone()),
+            quick_check,
+            last_recovery: LAST_RECOVERY
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone(),
+            database_path: path.clone(),
+        })
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn empty_database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        db::register_functions(&conn).unwrap();
+        migrations::run(&mut conn).unwrap();
+        conn
+    }
+    fn database() -> Connection {
+        let conn = empty_database();
+        conn.execute_batch(
+            "INSERT INTO paste_sheets (id, content, directory_id)
+             SELECT column1, column2, (SELECT id FROM directories WHERE name = 'Clipboard')
+             FROM (VALUES (1, 'first'), (2, 'second'), (3, 'third'))",
+        )
+        .unwrap();
+        conn
+    }
+    #[test]
+    fn recovery_keeps_changes_made_after_the_backup() {
+        let backup = database();
+        let damaged = database();
+        // The deleted row stands in for one that sat on an unreadable page.
+        damaged
+            .execute_batch(
+                "UPDATE paste_sheets SET content = 'first, edited' WHERE id = 1;
+                 UPDATE paste_sheets SET deleted_at = CURRENT_TIMESTAMP WHERE id = 2;
+                 DELETE FROM paste_sheets WHERE id = 3;
+                 INSERT INTO paste_sheets (id, content, directory_id)
+                 SELECT 4, 'fourth', directory_id FROM paste_sheets WHERE id = 1;",
+            )
+            .unwrap();
+        let recovered = empty_database();
+        salvage(Some(&damaged), Some(&backup), &recovered).unwrap();
+        let items: Vec<(i64, String, bool)> = recovered
+            .prepare("SELECT id, content, deleted_at IS NOT NULL FROM paste_sheets ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                (1, "first, edited".to_string(), false),
+                (2, "second".to_string(), true),
+                (3, "third".to_string(), false),
+                (4, "fourth".to_string(), false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,571 @@
+use crate::modules::frecency;
+use rusqlite::{Connection, Result, Transaction};
+type Migration = fn(&Transaction) -> Result<()>;
+const MIGRATIONS: &[Migration] = &[
+    baseline_schema,
+    full_text_index,
+    history_indexes,
+    directory_references,
+    soft_delete,
+    pinned_items,
+    item_tags,
+    item_revisions,
+    usage_timestamps,
+    frecency_scores,
+    directory_hierarchy,
+    manual_ordering,
+    content_hashes,
+    retention_policies,
+    portable_content_hashes,
+    sibling_directory_names,
+];
+const FTS_TRIGGERS: &str = "
+    CREATE TRIGGER IF NOT EXISTS paste_sheets_fts_insert AFTER INSERT ON paste_sheets BEGIN
+        INSERT INTO paste_sheets_fts (rowid, content, memo)
+        VALUES (new.id, new.content, new.memo);
+    END;
+    CREATE TRIGGER IF NOT EXISTS paste_sheets_fts_delete AFTER DELETE ON paste_sheets BEGIN
+        INSERT INTO paste_sheets_fts (paste_sheets_fts, rowid, content, memo)
+        VALUES ('delete', old.id, old.content, old.memo);
+    END;
+    CREATE TRIGGER IF NOT EXISTS paste_sheets_fts_update
+    AFTER UPDATE OF content, memo ON paste_sheets BEGIN
+        INSERT INTO paste_sheets_fts (paste_sheets_fts, rowid, content, memo)
+        VALUES ('delete', old.id, old.content, old.memo);
+        INSERT INTO paste_sheets_fts (rowid, content, memo)
+        VALUES (new.id, new.content, new.memo);
+    END;";
+const DIRECTORY_POSITION_TRIGGER: &str = "
+    CREATE TRIGGER IF NOT EXISTS directories_default_position
+    AFTER INSERT ON directories WHEN new.position IS NULL BEGIN
+        UPDATE directories SET position = (
+            SELECT COALESCE(MAX(position), 0) + 1 FROM directories
+            WHERE parent_id IS new.parent_id AND id != new.id
+        )
+        WHERE id = new.id;
+    END;";
+pub fn latest_version() -> i64 {
+    MIGRATIONS.len() as i64
+}
+pub fn current_version(conn: &Connection) -> Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+pub fn run(conn: &mut Connection) -> Result<()> {
+    let current = current_version(conn)?;
+    if current > latest_version() {
+        log::warn!(
+            "[DB] Schema version {} is newer than this build ({})",
+            current,
+            latest_version()
+        );
+        return Ok(());
+    }
+    conn.pragma_update(None, "foreign_keys", "OFF")?;
+    let result = apply_pending(conn, current);
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    result
+}
+fn apply_pending(conn: &mut Connection, current: i64) -> Result<()> {
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as i64 + 1;
+        let tx = conn.transaction()?;
+        migration(&tx)?;
+        let violations: i64 =
+            tx.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+                row.get(0)
+            })?;
+        if violations > 0 {
+            log::error!(
+                "[DB] Migration {} left {} foreign key violations",
+                version,
+                violations
+            );
+            return Err(rusqlite::Error::InvalidQuery);
+        }
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+        log::info!("[DB] Migrated schema to version {}", version);
+    }
+    Ok(())
+}
+fn has_column(tx: &Transaction, table: &str, column: &str) -> Result<bool> {
+    let count: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+fn baseline_schema(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS directories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        INSERT OR IGNORE INTO directories (name) VALUES ('Clipboard');
+        CREATE TABLE IF NOT EXISTS paste_sheets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            content TEXT NOT NULL,
+            directory TEXT NOT NULL,
+            memo TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (directory) REFERENCES directories(name)
+        );",
+    )?;
+    if !has_column(tx, "paste_sheets", "memo")? {
+        tx.execute("ALTER TABLE paste_sheets ADD COLUMN memo TEXT", [])?;
+    }
+    tx.execute_batch(
+        "INSERT OR IGNORE INTO directories (name)
+         SELECT DISTINCT directory FROM paste_sheets;
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        INSERT OR IGNORE INTO settings (key, value) VALUES ('mouse_edge_enabled', 'true');",
+    )
+}
+fn full_text_index(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS paste_sheets_fts USING fts5(
+            content,
+            memo,
+            content = 'paste_sheets',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );",
+    )?;
+    tx.execute_batch(FTS_TRIGGERS)?;
+    tx.execute(
+        "INSERT INTO paste_sheets_fts (paste_sheets_fts) VALUES ('rebuild')",
+        [],
+    )?;
+    Ok(())
+}
+fn history_indexes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_paste_sheets_created
+            ON paste_sheets (created_at DESC, id DESC);
+        CREATE INDEX IF NOT EXISTS idx_paste_sheets_directory_created
+            ON paste_sheets (directory, created_at DESC, id DESC);",
+    )
+}
+fn directory_references(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "INSERT OR IGNORE INTO directories (name)
+         SELECT DISTINCT directory FROM paste_sheets;
+        CREATE TABLE paste_sheets_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            content TEXT NOT NULL,
+            directory_id INTEGER NOT NULL
+                REFERENCES directories(id) ON UPDATE CASCADE ON DELETE CASCADE,
+            memo TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        INSERT INTO paste_sheets_new (id, content, directory_id, memo, created_at)
+        SELECT p.id, p.content, d.id, p.memo, p.created_at
+        FROM paste_sheets p
+        JOIN directories d ON d.name = p.directory;
+        DROP TABLE paste_sheets;
+        ALTER TABLE paste_sheets_new RENAME TO paste_sheets;
+        CREATE INDEX idx_paste_sheets_created
+            ON paste_sheets (created_at DESC, id DESC);
+        CREATE INDEX idx_paste_sheets_directory_created
+            ON paste_sheets (directory_id, created_at DESC, id DESC);",
+    )?;
+    tx.execute_batch(FTS_TRIGGERS)?;
+    tx.execute(
+        "INSERT INTO paste_sheets_fts (paste_sheets_fts) VALUES ('rebuild')",
+        [],
+    )?;
+    Ok(())
+}
+fn soft_delete(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE paste_sheets ADD COLUMN deleted_at TIMESTAMP;
+        ALTER TABLE directories ADD COLUMN deleted_at TIMESTAMP;
+        CREATE INDEX idx_paste_sheets_deleted ON paste_sheets (deleted_at)
+            WHERE deleted_at IS NOT NULL;
+        INSERT OR IGNORE INTO settings (key, value) VALUES ('trash_retention_days', '30');",
+    )
+}
+fn pinned_items(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE paste_sheets ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+        DROP INDEX IF EXISTS idx_paste_sheets_created;
+        DROP INDEX IF EXISTS idx_paste_sheets_directory_created;
+        CREATE INDEX idx_paste_sheets_pinned_created
+            ON paste_sheets (pinned DESC, created_at DESC, id DESC);
+        CREATE INDEX idx_paste_sheets_directory_pinned_created
+            ON paste_sheets (directory_id, pinned DESC, created_at DESC, id DESC);",
+    )
+}
+fn item_tags(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE item_tags (
+            item_id INTEGER NOT NULL REFERENCES paste_sheets(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (item_id, tag_id)
+        ) WITHOUT ROWID;
+        CREATE INDEX idx_item_tags_tag ON item_tags (tag_id, item_id);",
+    )
+}
+fn item_revisions(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE item_revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            item_id INTEGER NOT NULL REFERENCES paste_sheets(id) ON DELETE CASCADE,
+            content TEXT NOT NULL,
+            directory_id INTEGER REFERENCES directories(id) ON DELETE SET NULL,
+            memo TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX idx_item_revisions_item ON item_revisions (item_id, id DESC);
+        CREATE TRIGGER item_revisions_record
+        AFTER UPDATE OF content, directory_id, memo ON paste_sheets
+        WHEN old.content IS NOT new.content
+          OR old.directory_id IS NOT new.directory_id
+          OR old.memo IS NOT new.memo
+        BEGIN
+            INSERT INTO item_revisions (item_id, content, directory_id, memo)
+            VALUES (old.id, old.content, old.directory_id, old.memo);
+            DELETE FROM item_revisions
+            WHERE item_id = old.id AND id NOT IN (
+                SELECT id FROM item_revisions WHERE item_id = old.id ORDER BY id DESC LIMIT 50
+            );
+        END;",
+    )
+}
+fn usage_timestamps(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE paste_sheets ADD COLUMN updated_at TIMESTAMP;
+        ALTER TABLE paste_sheets ADD COLUMN last_used_at TIMESTAMP;
+        ALTER TABLE paste_sheets ADD COLUMN use_count INTEGER NOT NULL DEFAULT 0;
+        UPDATE paste_sheets SET updated_at = created_at;
+        CREATE TRIGGER paste_sheets_default_updated_at
+        AFTER INSERT ON paste_sheets WHEN new.updated_at IS NULL BEGIN
+            UPDATE paste_sheets SET updated_at = new.created_at WHERE id = new.id;
+        END;
+        CREATE INDEX idx_paste_sheets_pinned_updated
+            ON paste_sheets (pinned DESC, updated_at DESC, id DESC);
+        CREATE INDEX idx_paste_sheets_directory_pinned_updated
+            ON paste_sheets (directory_id, pinned DESC, updated_at DESC, id DESC);",
+    )
+}
+fn frecency_scores(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE paste_sheets ADD COLUMN frecency REAL;
+        CREATE TRIGGER paste_sheets_default_frecency
+        AFTER INSERT ON paste_sheets WHEN new.frecency IS NULL BEGIN
+            UPDATE paste_sheets SET frecency = CAST(strftime('%s', new.created_at) AS REAL)
+            WHERE id = new.id;
+        END;
+        CREATE INDEX idx_paste_sheets_pinned_frecency
+            ON paste_sheets (pinned DESC, frecency DESC, id DESC);",
+    )?;
+    let rows: Vec<(i64, f64, Option<f64>, i64)> = {
+        let mut stmt = tx.prepare(
+            "SELECT id,
+                CAST(strftime('%s', created_at) AS REAL),
+                CAST(strftime('%s', last_used_at) AS REAL),
+                use_count
+             FROM paste_sheets",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?;
+        rows.collect::<Result<_>>()?
+    };
+    let mut update = tx.prepare("UPDATE paste_sheets SET frecency = ?1 WHERE id = ?2")?;
+    for (id, created_at, last_used_at, use_count) in rows {
+        let mut score = frecency::bump(None, created_at);
+        if let Some(last_used_at) = last_used_at {
+            for _ in 0..use_count {
+                score = frecency::bump(Some(score), last_used_at);
+            }
+        }
+        update.execute(rusqlite::params![score, id])?;
+    }
+    Ok(())
+}
+fn directory_hierarchy(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE directories ADD COLUMN parent_id INTEGER
+            REFERENCES directories(id) ON DELETE CASCADE;
+        CREATE INDEX idx_directories_parent ON directories (parent_id);",
+    )
+}
+fn manual_ordering(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE directories ADD COLUMN position INTEGER;
+        ALTER TABLE paste_sheets ADD COLUMN position INTEGER;
+        UPDATE directories SET position = ranked.position
+        FROM (
+            SELECT id, row_number() OVER (PARTITION BY parent_id ORDER BY created_at, id) AS position
+            FROM directories
+        ) AS ranked
+        WHERE ranked.id = directories.id;
+        UPDATE paste_sheets SET position = ranked.position
+        FROM (
+            SELECT id, row_number() OVER (
+                PARTITION BY directory_id ORDER BY pinned DESC, updated_at DESC, id DESC
+            ) AS position
+            FROM paste_sheets
+        ) AS ranked
+        WHERE ranked.id = paste_sheets.id;
+        CREATE TRIGGER paste_sheets_default_position
+        AFTER INSERT ON paste_sheets WHEN new.position IS NULL BEGIN
+            UPDATE paste_sheets SET position = (
+                SELECT COALESCE(MIN(position), 1) - 1 FROM paste_sheets
+                WHERE directory_id = new.directory_id AND id != new.id
+            )
+            WHERE id = new.id;
+        END;
+        CREATE TRIGGER paste_sheets_moved_position
+        AFTER UPDATE OF directory_id ON paste_sheets
+        WHEN new.directory_id != old.directory_id BEGIN
+            UPDATE paste_sheets SET position = (
+                SELECT COALESCE(MIN(position), 1) - 1 FROM paste_sheets
+                WHERE directory_id = new.directory_id AND id != new.id
+            )
+            WHERE id = new.id;
+        END;
+        CREATE INDEX idx_paste_sheets_directory_position
+            ON paste_sheets (directory_id, pinned DESC, position, id DESC);",
+    )?;
+    tx.execute_batch(DIRECTORY_POSITION_TRIGGER)
+}
+// `content_hash()` is registered on every connection by `db::open_connection`.
+fn content_hashes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE paste_sheets ADD COLUMN content_hash INTEGER;
+        ALTER TABLE directories ADD COLUMN unique_content INTEGER NOT NULL DEFAULT 0;
+        UPDATE paste_sheets SET content_hash = content_hash(content);
+        CREATE TRIGGER paste_sheets_hash_insert AFTER INSERT ON paste_sheets BEGIN
+            UPDATE paste_sheets SET content_hash = content_hash(new.content) WHERE id = new.id;
+        END;
+        CREATE TRIGGER paste_sheets_hash_update AFTER UPDATE OF content ON paste_sheets BEGIN
+            UPDATE paste_sheets SET content_hash = content_hash(new.content) WHERE id = new.id;
+        END;
+        CREATE TRIGGER paste_sheets_unique_insert BEFORE INSERT ON paste_sheets
+        WHEN new.deleted_at IS NULL
+            AND (SELECT unique_content FROM directories WHERE id = new.directory_id)
+        BEGIN
+            SELECT RAISE(ABORT, 'duplicate content') WHERE EXISTS (
+                SELECT 1 FROM paste_sheets
+                WHERE directory_id = new.directory_id AND deleted_at IS NULL
+                  AND content_hash = content_hash(new.content) AND content = new.content
+            );
+        END;
+        CREATE TRIGGER paste_sheets_unique_update
+        BEFORE UPDATE OF content, directory_id, deleted_at ON paste_sheets
+        WHEN new.deleted_at IS NULL
+            AND (SELECT unique_content FROM directories WHERE id = new.directory_id)
+        BEGIN
+            SELECT RAISE(ABORT, 'duplicate content') WHERE EXISTS (
+                SELECT 1 FROM paste_sheets
+                WHERE directory_id = new.directory_id AND deleted_at IS NULL AND id != new.id
+                  AND content_hash = content_hash(new.content) AND content = new.content
+            );
+        END;
+        CREATE INDEX idx_paste_sheets_directory_hash ON paste_sheets (directory_id, content_hash);",
+    )
+}
+fn retention_policies(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE directories ADD COLUMN retention_max_items INTEGER;
+        ALTER TABLE directories ADD COLUMN retention_max_age_days INTEGER;
+        ALTER TABLE directories ADD COLUMN retention_max_bytes INTEGER;
+        ALTER TABLE directories ADD COLUMN retention_keep_forever INTEGER NOT NULL DEFAULT 0;
+        UPDATE directories SET retention_max_items = 30 WHERE name = 'Clipboard';
+        CREATE INDEX idx_paste_sheets_retention
+            ON paste_sheets (directory_id, updated_at DESC, id DESC)
+            WHERE deleted_at IS NULL AND pinned = 0;",
+    )
+}
+// Hashes and the unique-content rule are maintained by the application, so connections
+// that never registered `content_hash()`, such as the sqlite3 shell, can still write rows.
+fn portable_content_hashes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "DROP TRIGGER IF EXISTS paste_sheets_hash_insert;
+        DROP TRIGGER IF EXISTS paste_sheets_hash_update;
+        DROP TRIGGER IF EXISTS paste_sheets_unique_insert;
+        DROP TRIGGER IF EXISTS paste_sheets_unique_update;",
+    )
+}
+// Names only have to be unique among active siblings, so the table-wide UNIQUE constraint
+// from the baseline schema is replaced by a partial index over (parent, name). A trashed
+// directory no longer blocks its name.
+fn sibling_directory_names(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE directories_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            deleted_at TIMESTAMP,
+            parent_id INTEGER REFERENCES directories(id) ON DELETE CASCADE,
+            position INTEGER,
+            unique_content INTEGER NOT NULL DEFAULT 0,
+            retention_max_items INTEGER,
+            retention_max_age_days INTEGER,
+            retention_max_bytes INTEGER,
+            retention_keep_forever INTEGER NOT NULL DEFAULT 0
+        );
+        INSERT INTO directories_new (
+            id, name, created_at, deleted_at, parent_id, position, unique_content,
+            retention_max_items, retention_max_age_days, retention_max_bytes,
+            retention_keep_forever
+        )
+        SELECT id, name, created_at, deleted_at, parent_id, position, unique_content,
+            retention_max_items, retention_max_age_days, retention_max_bytes,
+            retention_keep_forever
+        FROM directories;
+        DROP TABLE directories;
+        ALTER TABLE directories_new RENAME TO directories;
+        CREATE INDEX idx_directories_parent ON directories (parent_id);
+        CREATE UNIQUE INDEX idx_directories_sibling_name
+            ON directories (COALESCE(parent_id, 0), name) WHERE deleted_at IS NULL;",
+    )?;
+    tx.execute_batch(DIRECTORY_POSITION_TRIGGER)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::db;
+    fn legacy_database(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("paste_sheets_{}_{}.db", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        remove_database(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE directories (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO directories (name) VALUES ('Clipboard'), ('Work');
+            CREATE TABLE paste_sheets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                content TEXT NOT NULL,
+                directory TEXT NOT NULL,
+                memo TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (directory) REFERENCES directories(name)
+            );
+            INSERT INTO paste_sheets (content, directory, memo, created_at) VALUES
+                ('copied text', 'Clipboard', NULL, '2026-01-01 10:00:00'),
+                ('SELECT 1;', 'Work', 'health check', '2026-01-02 11:00:00');
+            CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO settings (key, value) VALUES ('mouse_edge_enabled', 'false');",
+        )
+        .unwrap();
+        path
+    }
+    fn remove_database(path: &str) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+    #[test]
+    fn upgrades_legacy_database_without_data_loss() {
+        let path = legacy_database("upgrade");
+        // Not `db::open_connection`: that applies the process-wide passphrase, which the
+        // encryption tests set while this one runs.
+        let mut conn = Connection::open(&path).unwrap();
+        db::register_functions(&conn).unwrap();
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        let items: Vec<(String, String, Option<String>, String)> = conn
+            .prepare(
+                "SELECT p.content, d.name, p.memo, p.created_at
+                 FROM paste_sheets p
+                 JOIN directories d ON d.id = p.directory_id
+                 ORDER BY p.id",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                (
+                    "copied text".to_string(),
+                    "Clipboard".to_string(),
+                    None,
+                    "2026-01-01 10:00:00".to_string()
+                ),
+                (
+                    "SELECT 1;".to_string(),
+                    "Work".to_string(),
+                    Some("health check".to_string()),
+                    "2026-01-02 11:00:00".to_string()
+                ),
+            ]
+        );
+        let setting: String = conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'mouse_edge_enabled'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(setting, "false");
+        let stale_hashes: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM paste_sheets WHERE content_hash IS NOT content_hash(content)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stale_hashes, 0);
+        let shell = Connection::open(&path).unwrap();
+        shell
+            .execute(
+                "INSERT INTO paste_sheets (content, directory_id) VALUES ('from the shell', 1)",
+                [],
+            )
+            .unwrap();
+        shell
+            .execute(
+                "UPDATE paste_sheets SET content = 'edited in the shell'
+                 WHERE content = 'from the shell'",
+                [],
+            )
+            .unwrap();
+        drop(shell);
+        assert_eq!(db::fill_content_hashes(&conn).unwrap(), 1);
+        conn.execute_batch(
+            "INSERT INTO directories (name) VALUES ('ProjA'), ('ProjB');
+            INSERT INTO directories (name, parent_id)
+            SELECT 'prod', id FROM directories WHERE name IN ('ProjA', 'ProjB');",
+        )
+        .unwrap();
+        assert!(conn
+            .execute(
+                "INSERT INTO directories (name, parent_id)
+                 SELECT 'prod', id FROM directories WHERE name = 'ProjA'",
+                [],
+            )
+            .is_err());
+        conn.execute_batch(
+            "UPDATE directories SET deleted_at = CURRENT_TIMESTAMP WHERE name = 'ProjA';
+            INSERT INTO directories (name) VALUES ('ProjA');",
+        )
+        .unwrap();
+        run(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        drop(conn);
+        remove_database(&path);
+    }
+}
@@ -1,4 +1,21 @@
+pub mod backup;
+pub mod bulk;
 pub mod db;
+pub mod encryption;
+pub mod error;
+pub mod export;
+pub mod frecency;
+pub mod import;
+pub mod location;
+pub mod maintenance;
+pub mod migrations;
+pub mod ordering;
+pub mod retention;
+pub mod revisions;
+pub mod search;
+pub mod settings;
+pub mod tags;
+pub mod trash;
 pub mod clipboard;
 pub mod hotkey;
 pub mod window_manager;
@@ -0,0 +1,113 @@
+use crate::modules::db;
+use crate::modules::error::{AppError, AppResult};
+use rusqlite::{OptionalExtension, Result, Transaction};
+const CLIPBOARD_DIRECTORY: &str = "Clipboard";
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Placement<T> {
+    Before(T),
+    After(T),
+    Index(usize),
+}
+fn place<T: PartialEq>(
+    mut siblings: Vec<T>,
+    moving: T,
+    placement: &Placement<T>,
+) -> AppResult<Vec<T>> {
+    let index = match placement {
+        Placement::Index(index) => (*index).min(siblings.len()),
+        Placement::Before(target) | Placement::After(target) => {
+            if *target == moving {
+                return Err(AppError::Validation(
+                    "Cannot place an entry relative to itself".to_string(),
+                ));
+            }
+            let found = siblings
+                .iter()
+                .position(|sibling| sibling == target)
+                .ok_or_else(|| AppError::not_found("Placement target"))?;
+            match placement {
+                Placement::After(_) => found + 1,
+                _ => found,
+            }
+        }
+    };
+    siblings.insert(index, moving);
+    Ok(siblings)
+}
+fn write_positions(tx: &Transaction, table: &str, ids: &[i64]) -> Result<()> {
+    let mut stmt =
+        tx.prepare_cached(&format!("UPDATE {} SET position = ?1 WHERE id = ?2", table))?;
+    for (index, id) in ids.iter().enumerate() {
+        stmt.execute([index as i64 + 1, *id])?;
+    }
+    Ok(())
+}
+pub fn reorder_directory(id: i64, placement: Placement<i64>) -> AppResult<()> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let (name, parent_id): (String, Option<i64>) = tx
+            .query_row(
+                "SELECT name, parent_id FROM directories WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("Directory"))?;
+        if name == CLIPBOARD_DIRECTORY {
+            return Err(AppError::ReservedName(
+                "Clipboard always stays at the top".to_string(),
+            ));
+        }
+        let siblings: Vec<i64> = {
+            let mut stmt = tx.prepare_cached(
+                "SELECT id FROM directories
+                 WHERE parent_id IS ?1 AND deleted_at IS NULL AND id != ?2 AND name != ?3
+                 ORDER BY position, created_at, id",
+            )?;
+            let rows = stmt.query_map(
+                rusqlite::params![parent_id, id, CLIPBOARD_DIRECTORY],
+                |row| row.get(0),
+            )?;
+            rows.collect::<Result<_>>()?
+        };
+        let ordered = place(siblings, id, &placement)?;
+        write_positions(&tx, "directories", &ordered)?;
+        tx.commit()?;
+        Ok(())
+    })
+}
+pub fn reorder_item(id: i64, placement: Placement<i64>) -> AppResult<()> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let (directory_id, directory): (i64, String) = tx
+            .query_row(
+                &format!(
+                    "SELECT p.directory_id, d.name FROM {} WHERE p.id = ?1",
+                    db::ITEM_SOURCE
+                ),
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("Item"))?;
+        if directory == CLIPBOARD_DIRECTORY {
+            return Err(AppError::ReservedName(
+                "Clipboard items are ordered by recency".to_string(),
+            ));
+        }
+        let siblings: Vec<i64> = {
+            let mut stmt = tx.prepare_cached(
+                "SELECT id FROM paste_sheets
+                 WHERE directory_id = ?1 AND deleted_at IS NULL AND id != ?2
+                 ORDER BY pinned DESC, position, id DESC",
+            )?;
+            let rows = stmt.query_map([directory_id, id], |row| row.get(0))?;
+            rows.collect::<Result<_>>()?
+        };
+        let ordered = place(siblings, id, &placement)?;
+        write_positions(&tx, "paste_sheets", &ordered)?;
+        tx.commit()?;
+        Ok(())
+    })
+}
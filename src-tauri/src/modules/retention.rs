@@ -0,0 +1,138 @@
+use crate::modules::db;
+use crate::modules::error::{AppError, AppResult};
+use log::{error, info};
+use rusqlite::{Connection, Result};
+use std::thread;
+use std::time::Duration;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+#[derive(Clone, Copy, Default, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RetentionPolicy {
+    pub max_items: Option<i64>,
+    pub max_age_days: Option<i64>,
+    pub max_total_bytes: Option<i64>,
+    pub keep_forever: bool,
+}
+impl RetentionPolicy {
+    fn validate(&self) -> AppResult<()> {
+        let limits = [
+            ("max_items", self.max_items),
+            ("max_age_days", self.max_age_days),
+            ("max_total_bytes", self.max_total_bytes),
+        ];
+        for (name, limit) in limits {
+            if limit.is_some_and(|limit| limit < 1) {
+                return Err(AppError::Validation(format!("{} must be at least 1", name)));
+            }
+        }
+        Ok(())
+    }
+    fn is_unlimited(&self) -> bool {
+        self.keep_forever
+            || (self.max_items.is_none()
+                && self.max_age_days.is_none()
+                && self.max_total_bytes.is_none())
+    }
+}
+fn read_policy(conn: &Connection, directory_id: i64) -> Result<RetentionPolicy> {
+    conn.prepare_cached(
+        "SELECT retention_max_items, retention_max_age_days, retention_max_bytes,
+            retention_keep_forever
+         FROM directories WHERE id = ?1",
+    )?
+    .query_row([directory_id], |row| {
+        Ok(RetentionPolicy {
+            max_items: row.get(0)?,
+            max_age_days: row.get(1)?,
+            max_total_bytes: row.get(2)?,
+            keep_forever: row.get(3)?,
+        })
+    })
+}
+// Pinned items never count towards a limit and are never trashed. Everything else is
+// ranked newest first, so the items beyond a limit are always the oldest ones.
+pub fn enforce(conn: &Connection, directory_id: i64) -> Result<usize> {
+    let policy = read_policy(conn, directory_id)?;
+    if policy.is_unlimited() {
+        return Ok(0);
+    }
+    conn.prepare_cached(
+        "WITH ranked AS (
+            SELECT id, updated_at,
+                row_number() OVER newest AS rank,
+                SUM(length(CAST(content AS BLOB))) OVER newest AS running_bytes
+            FROM paste_sheets
+            WHERE directory_id = ?1 AND deleted_at IS NULL AND pinned = 0
+            WINDOW newest AS (ORDER BY updated_at DESC, id DESC)
+         )
+         UPDATE paste_sheets SET deleted_at = CURRENT_TIMESTAMP
+         WHERE id IN (
+            SELECT id FROM ranked
+            WHERE rank > ?2
+               OR running_bytes > ?3
+               OR (?4 IS NOT NULL AND updated_at < datetime('now', printf('-%d days', ?4)))
+         )",
+    )?
+    .execute(rusqlite::params![
+        directory_id,
+        policy.max_items,
+        policy.max_total_bytes,
+        policy.max_age_days
+    ])
+}
+pub fn get_policy(directory_id: i64) -> AppResult<RetentionPolicy> {
+    db::with_conn(|conn| {
+        db::directory_name(conn, directory_id)?;
+        Ok(read_policy(conn, directory_id)?)
+    })
+}
+pub fn set_policy(directory_id: i64, policy: RetentionPolicy) -> AppResult<usize> {
+    policy.validate()?;
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        db::directory_name(&tx, directory_id)?;
+        tx.execute(
+            "UPDATE directories SET
+                retention_max_items = ?1,
+                retention_max_age_days = ?2,
+                retention_max_bytes = ?3,
+                retention_keep_forever = ?4
+             WHERE id = ?5",
+            rusqlite::params![
+                policy.max_items,
+                policy.max_age_days,
+                policy.max_total_bytes,
+                policy.keep_forever,
+                directory_id
+            ],
+        )?;
+        let trashed = enforce(&tx, directory_id)?;
+        tx.commit()?;
+        Ok(trashed)
+    })
+}
+pub fn enforce_all() -> AppResult<usize> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let directory_ids: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id FROM directories WHERE deleted_at IS NULL")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_>>()?
+        };
+        let mut trashed = 0;
+        for directory_id in directory_ids {
+            trashed += enforce(&tx, directory_id)?;
+        }
+        tx.commit()?;
+        Ok(trashed)
+    })
+}
+pub fn start_retention_sweeper() {
+    thread::spawn(|| loop {
+        match enforce_all() {
+            Ok(0) => {}
+            Ok(trashed) => info!("Retention moved {} items to the trash", trashed),
+            Err(e) => error!("Failed to apply retention policies: {:?}", e),
+        }
+        thread::sleep(SWEEP_INTERVAL);
+    });
+}
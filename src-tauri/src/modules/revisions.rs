@@ -0,0 +1,101 @@
+use crate::modules::db;
+use crate::modules::error::{AppError, AppResult};
+use crate::modules::retention;
+use rusqlite::OptionalExtension;
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ItemRevision {
+    pub id: i64,
+    pub item_id: i64,
+    pub content: String,
+    pub directory_id: Option<i64>,
+    pub directory: Option<String>,
+    pub memo: Option<String>,
+    pub created_at: String,
+}
+pub fn get_item_revisions(item_id: i64) -> AppResult<Vec<ItemRevision>> {
+    db::with_conn(|conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT r.id, r.item_id, r.content, r.directory_id, d.name, r.memo, r.created_at
+             FROM item_revisions r
+             LEFT JOIN directories d ON d.id = r.directory_id
+             WHERE r.item_id = ?1
+             ORDER BY r.id DESC",
+        )?;
+        let rows = stmt.query_map([item_id], |row| {
+            Ok(ItemRevision {
+                id: row.get(0)?,
+                item_id: row.get(1)?,
+                content: row.get(2)?,
+                directory_id: row.get(3)?,
+                directory: row.get(4)?,
+                memo: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    })
+}
+pub fn restore_item_revision(revision_id: i64) -> AppResult<i64> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let (item_id, content, memo, directory_id, trashed): (
+            i64,
+            String,
+            Option<String>,
+            i64,
+            bool,
+        ) = tx
+            .query_row(
+                "SELECT r.item_id, r.content, r.memo,
+                    COALESCE(
+                        (SELECT d.id FROM directories d
+                         WHERE d.id = r.directory_id AND d.deleted_at IS NULL),
+                        p.directory_id
+                    ),
+                    p.deleted_at IS NOT NULL
+                 FROM item_revisions r JOIN paste_sheets p ON p.id = r.item_id
+                 WHERE r.id = ?1",
+                [revision_id],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("Revision"))?;
+        if trashed {
+            return Err(AppError::not_found("Item"));
+        }
+        if db::has_duplicate(&tx, directory_id, &content, Some(item_id))? {
+            return Err(AppError::duplicate_content());
+        }
+        tx.execute(
+            "UPDATE paste_sheets SET
+                content = ?1,
+                content_hash = ?2,
+                memo = ?3,
+                directory_id = ?4,
+                updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?5",
+            rusqlite::params![
+                content,
+                db::content_hash(&content),
+                memo,
+                directory_id,
+                item_id
+            ],
+        )?;
+        retention::enforce(&tx, directory_id)?;
+        tx.commit()?;
+        Ok(item_id)
+    })
+}
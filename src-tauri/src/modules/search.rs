@@ -0,0 +1,118 @@
+use crate::modules::db::{self, PasteItem, ITEM_COLUMNS, ITEM_SOURCE, MAX_PAGE_SIZE};
+use crate::modules::error::AppResult;
+const DEFAULT_LIMIT: i64 = 50;
+const SNIPPET_TOKENS: i64 = 16;
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+#[derive(Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    #[default]
+    Relevance,
+    Frecency,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct MatchRange {
+    pub start: usize,
+    pub end: usize,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SearchResult {
+    pub item: PasteItem,
+    pub snippet: String,
+    pub snippet_highlights: Vec<MatchRange>,
+    pub content_highlights: Vec<MatchRange>,
+    pub score: f64,
+}
+pub fn build_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+// Offsets are UTF-16 code units so the frontend can slice strings directly.
+fn strip_markers(marked: &str) -> (String, Vec<MatchRange>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    let mut start = None;
+    for c in marked.chars() {
+        match c {
+            MATCH_START => start = Some(offset),
+            MATCH_END => {
+                if let Some(s) = start.take() {
+                    ranges.push(MatchRange {
+                        start: s,
+                        end: offset,
+                    });
+                }
+            }
+            _ => {
+                offset += c.len_utf16();
+                text.push(c);
+            }
+        }
+    }
+    (text, ranges)
+}
+pub fn search_items(
+    query: &str,
+    directory_id: Option<i64>,
+    sort: SearchSort,
+    limit: Option<i64>,
+) -> AppResult<Vec<SearchResult>> {
+    let match_query = match build_match_query(query) {
+        Some(q) => q,
+        None => return Ok(Vec::new()),
+    };
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_PAGE_SIZE);
+    let start = MATCH_START.to_string();
+    let end = MATCH_END.to_string();
+    let order = match sort {
+        SearchSort::Relevance => "score",
+        SearchSort::Frecency => "COALESCE(p.frecency, 0.0) DESC, score",
+    };
+    db::with_conn(|conn| {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {},
+                snippet(paste_sheets_fts, -1, ?4, ?5, '…', ?6) AS snippet,
+                highlight(paste_sheets_fts, 0, ?4, ?5) AS marked_content,
+                bm25(paste_sheets_fts, 1.0, 2.0) AS score
+             FROM {}
+             JOIN paste_sheets_fts ON paste_sheets_fts.rowid = p.id
+             WHERE paste_sheets_fts MATCH ?1
+               AND (?2 IS NULL OR p.directory_id = ?2)
+             ORDER BY {}
+             LIMIT ?3",
+            ITEM_COLUMNS, ITEM_SOURCE, order
+        ))?;
+        let rows = stmt.query_map(
+            rusqlite::params![match_query, directory_id, limit, start, end, SNIPPET_TOKENS],
+            |row| {
+                let item = db::map_paste_item(row)?;
+                let snippet: String = row.get("snippet")?;
+                let marked_content: String = row.get("marked_content")?;
+                let score: f64 = row.get("score")?;
+                let (snippet, snippet_highlights) = strip_markers(&snippet);
+                let (_, content_highlights) = strip_markers(&marked_content);
+                Ok(SearchResult {
+                    item,
+                    snippet,
+                    snippet_highlights,
+                    content_highlights,
+                    score: -score,
+                })
+            },
+        )?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    })
+}
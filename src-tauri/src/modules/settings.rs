@@ -0,0 +1,202 @@
+use crate::modules::db;
+use crate::modules::error::{AppError, AppResult};
+use log::{debug, warn};
+use std::sync::Mutex;
+pub const MOUSE_EDGE_ENABLED: &str = "mouse_edge_enabled";
+pub const TRASH_RETENTION_DAYS: &str = "trash_retention_days";
+pub const BACKUP_RETENTION_COUNT: &str = "backup_retention_count";
+pub const BACKUP_INTERVAL_HOURS: &str = "backup_interval_hours";
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum SettingKind {
+    Bool,
+    Integer { min: i64, max: i64 },
+}
+pub struct SettingDefinition {
+    pub key: &'static str,
+    pub kind: SettingKind,
+    pub default: &'static str,
+    pub description: &'static str,
+}
+pub const SETTINGS: &[SettingDefinition] = &[
+    SettingDefinition {
+        key: MOUSE_EDGE_ENABLED,
+        kind: SettingKind::Bool,
+        default: "true",
+        description: "Slide into the screen when the mouse hits the right edge.",
+    },
+    SettingDefinition {
+        key: TRASH_RETENTION_DAYS,
+        kind: SettingKind::Integer { min: 0, max: 3650 },
+        default: "30",
+        description: "Days to keep deleted items and folders before purging them.",
+    },
+    SettingDefinition {
+        key: BACKUP_RETENTION_COUNT,
+        kind: SettingKind::Integer { min: 1, max: 100 },
+        default: "7",
+        description: "Number of automatic backups to keep.",
+    },
+    SettingDefinition {
+        key: BACKUP_INTERVAL_HOURS,
+        kind: SettingKind::Integer { min: 1, max: 720 },
+        default: "24",
+        description: "Hours between automatic backups.",
+    },
+];
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SettingInfo {
+    pub key: String,
+    #[serde(flatten)]
+    pub kind: SettingKind,
+    pub default: String,
+    pub value: String,
+    pub description: String,
+}
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: String,
+}
+pub const CHANGE_EVENT: &str = "setting-changed";
+type Subscriber = Box<dyn Fn(&SettingChange) + Send + Sync>;
+static SUBSCRIBERS: Mutex<Vec<(Option<&'static str>, Subscriber)>> = Mutex::new(Vec::new());
+static PUBLISHED: Mutex<Vec<(&'static str, String)>> = Mutex::new(Vec::new());
+pub fn definition(key: &str) -> AppResult<&'static SettingDefinition> {
+    SETTINGS
+        .iter()
+        .find(|definition| definition.key == key)
+        .ok_or_else(|| {
+            warn!("[Settings] Unknown setting: {}", key);
+            AppError::not_found(&format!("Setting '{}'", key))
+        })
+}
+pub fn validate(key: &str, value: &str) -> AppResult<String> {
+    let definition = definition(key)?;
+    let value = value.trim();
+    let normalized = match definition.kind {
+        SettingKind::Bool => match value {
+            "true" | "false" => Some(value.to_string()),
+            _ => None,
+        },
+        SettingKind::Integer { min, max } => value
+            .parse::<i64>()
+            .ok()
+            .filter(|number| (min..=max).contains(number))
+            .map(|number| number.to_string()),
+    };
+    normalized.ok_or_else(|| {
+        warn!("[Settings] Invalid value for {}: {:?}", key, value);
+        AppError::Validation(format!("Invalid value {:?} for setting '{}'", value, key))
+    })
+}
+fn stored_or_default(definition: &SettingDefinition) -> String {
+    db::get_setting(definition.key)
+        .ok()
+        .flatten()
+        .and_then(|value| validate(definition.key, &value).ok())
+        .unwrap_or_else(|| definition.default.to_string())
+}
+pub fn get(key: &str) -> AppResult<String> {
+    Ok(stored_or_default(definition(key)?))
+}
+pub fn get_integer(key: &str) -> i64 {
+    get(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+pub fn all() -> Vec<SettingInfo> {
+    SETTINGS
+        .iter()
+        .map(|definition| SettingInfo {
+            key: definition.key.to_string(),
+            kind: definition.kind,
+            default: definition.default.to_string(),
+            value: stored_or_default(definition),
+            description: definition.description.to_string(),
+        })
+        .collect()
+}
+fn remember(key: &'static str, value: &str) -> Option<String> {
+    let mut published = PUBLISHED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match published.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, previous)) => Some(std::mem::replace(previous, value.to_string())),
+        None => {
+            published.push((key, value.to_string()));
+            None
+        }
+    }
+}
+fn dispatch(change: &SettingChange) {
+    let subscribers = SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (_, subscriber) in subscribers
+        .iter()
+        .filter(|(key, _)| key.is_none() || *key == Some(change.key.as_str()))
+    {
+        subscriber(change);
+    }
+}
+fn publish(definition: &SettingDefinition, old_value: Option<String>, new_value: String) {
+    let remembered = remember(definition.key, &new_value);
+    let old_value = old_value.or(remembered);
+    if old_value.as_deref() == Some(new_value.as_str()) {
+        return;
+    }
+    debug!("[Settings] {} = {}", definition.key, new_value);
+    dispatch(&SettingChange {
+        key: definition.key.to_string(),
+        old_value,
+        new_value,
+    });
+}
+// Key subscribers are called once with the current value and again after every change.
+pub fn subscribe<F>(key: &'static str, subscriber: F)
+where
+    F: Fn(&SettingChange) + Send + Sync + 'static,
+{
+    if let Ok(definition) = definition(key) {
+        let value = stored_or_default(definition);
+        remember(definition.key, &value);
+        subscriber(&SettingChange {
+            key: key.to_string(),
+            old_value: None,
+            new_value: value,
+        });
+    }
+    SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push((Some(key), Box::new(subscriber)));
+}
+pub fn subscribe_all<F>(subscriber: F)
+where
+    F: Fn(&SettingChange) + Send + Sync + 'static,
+{
+    for definition in SETTINGS {
+        remember(definition.key, &stored_or_default(definition));
+    }
+    SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push((None, Box::new(subscriber)));
+}
+pub fn update(key: &str, value: &str) -> AppResult<()> {
+    let definition = definition(key)?;
+    let value = validate(key, value)?;
+    let old_value = stored_or_default(definition);
+    db::set_setting(key, &value)?;
+    publish(definition, Some(old_value), value);
+    Ok(())
+}
+// Re-reads every setting after the database was swapped out underneath us.
+pub fn reload() {
+    for definition in SETTINGS {
+        publish(definition, None, stored_or_default(definition));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::modules::db;
+use crate::modules::error::{AppError, AppResult};
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TagInfo {
+    pub id: i64,
+    pub name: String,
+    pub count: i64,
+}
+pub fn get_tags() -> AppResult<Vec<TagInfo>> {
+    db::with_conn(|conn| {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT t.id, t.name, COUNT(active.id) as count
+             FROM tags t
+             LEFT JOIN item_tags it ON it.tag_id = t.id
+             LEFT JOIN (SELECT p.id FROM {}) active ON active.id = it.item_id
+             GROUP BY t.id
+             ORDER BY t.name COLLATE NOCASE",
+            db::ITEM_SOURCE
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(TagInfo {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                count: row.get(2)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    })
+}
+pub fn add_item_tag(item_id: i64, tag: &str) -> AppResult<()> {
+    let trimmed_tag = tag.trim();
+    if trimmed_tag.is_empty() {
+        return Err(AppError::Validation("Tag cannot be empty".to_string()));
+    }
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let item_exists: bool = tx.query_row(
+            &format!(
+                "SELECT EXISTS (SELECT 1 FROM {} WHERE p.id = ?1)",
+                db::ITEM_SOURCE
+            ),
+            [item_id],
+            |row| row.get(0),
+        )?;
+        if !item_exists {
+            return Err(AppError::not_found("Item"));
+        }
+        tx.execute(
+            "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+            [trimmed_tag],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+             SELECT ?1, id FROM tags WHERE name = ?2",
+            rusqlite::params![item_id, trimmed_tag],
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+}
+pub fn remove_item_tag(item_id: i64, tag: &str) -> AppResult<()> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let affected = tx.execute(
+            "DELETE FROM item_tags
+             WHERE item_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+            rusqlite::params![item_id, tag.trim()],
+        )?;
+        if affected == 0 {
+            return Err(AppError::not_found(&format!(
+                "Tag '{}' on item",
+                tag.trim()
+            )));
+        }
+        tx.execute(
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM item_tags)",
+            [],
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+}
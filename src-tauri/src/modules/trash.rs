@@ -0,0 +1,195 @@
+use crate::modules::db::{self, PasteItem, ITEM_COLUMNS};
+use crate::modules::error::{AppError, AppResult};
+use crate::modules::{retention, settings};
+use log::{error, info};
+use rusqlite::{OptionalExtension, Result, Transaction};
+use std::thread;
+use std::time::Duration;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TrashedItem {
+    pub item: PasteItem,
+    pub deleted_at: String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TrashedDirectory {
+    pub id: i64,
+    pub name: String,
+    pub count: i64,
+    pub deleted_at: String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TrashContents {
+    pub directories: Vec<TrashedDirectory>,
+    pub items: Vec<TrashedItem>,
+}
+pub fn list_trash() -> AppResult<TrashContents> {
+    db::with_conn(|conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT d.id, d.name, COUNT(p.id), d.deleted_at
+             FROM directories d
+             LEFT JOIN paste_sheets p ON p.directory_id = d.id AND p.deleted_at IS NULL
+             WHERE d.deleted_at IS NOT NULL
+             GROUP BY d.id
+             ORDER BY d.deleted_at DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(TrashedDirectory {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                count: row.get(2)?,
+                deleted_at: row.get(3)?,
+            })
+        })?;
+        let mut directories = Vec::new();
+        for row in rows {
+            directories.push(row?);
+        }
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {}, p.deleted_at AS trashed_at
+             FROM paste_sheets p JOIN directories d ON d.id = p.directory_id
+             WHERE p.deleted_at IS NOT NULL
+             ORDER BY p.deleted_at DESC, p.id DESC",
+            ITEM_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(TrashedItem {
+                item: db::map_paste_item(row)?,
+                deleted_at: row.get("trashed_at")?,
+            })
+        })?;
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?);
+        }
+        Ok(TrashContents { directories, items })
+    })
+}
+pub fn restore_item(id: i64) -> AppResult<()> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let (directory_id, directory_trashed, content): (i64, bool, String) = tx
+            .query_row(
+                "SELECT p.directory_id, d.deleted_at IS NOT NULL, p.content
+                 FROM paste_sheets p JOIN directories d ON d.id = p.directory_id
+                 WHERE p.id = ?1 AND p.deleted_at IS NOT NULL",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("Trashed item"))?;
+        let directory_id = if directory_trashed {
+            db::clipboard_directory_id(&tx)?
+        } else {
+            directory_id
+        };
+        if db::has_duplicate(&tx, directory_id, &content, Some(id))? {
+            return Err(AppError::duplicate_content());
+        }
+        // Restoring counts as an edit, otherwise retention would trash the item again.
+        tx.execute(
+            "UPDATE paste_sheets
+             SET deleted_at = NULL, directory_id = ?1, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?2",
+            [directory_id, id],
+        )?;
+        retention::enforce(&tx, directory_id)?;
+        tx.commit()?;
+        Ok(())
+    })
+}
+// Only active siblings hold a name, so another directory may have taken it while this one
+// was in the trash. The restored directory then gets the first free "name (n)".
+fn available_name(tx: &Transaction, name: &str, parent_id: Option<i64>) -> Result<String> {
+    let mut stmt = tx.prepare(
+        "SELECT EXISTS (
+            SELECT 1 FROM directories
+            WHERE parent_id IS ?1 AND name = ?2 AND deleted_at IS NULL
+         )",
+    )?;
+    let mut candidate = name.to_string();
+    let mut n = 1;
+    while stmt.query_row(rusqlite::params![parent_id, candidate], |row| row.get(0))? {
+        n += 1;
+        candidate = format!("{} ({})", name, n);
+    }
+    Ok(candidate)
+}
+pub fn restore_directory(id: i64) -> AppResult<()> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let (name, parent_id, deleted_at): (String, Option<i64>, String) = tx
+            .query_row(
+                "SELECT d.name, CASE WHEN parent.deleted_at IS NULL THEN d.parent_id END,
+                    d.deleted_at
+                 FROM directories d
+                 LEFT JOIN directories parent ON parent.id = d.parent_id
+                 WHERE d.id = ?1 AND d.deleted_at IS NOT NULL",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("Trashed directory"))?;
+        let restored_name = available_name(&tx, &name, parent_id)?;
+        if restored_name != name {
+            info!(
+                "Restoring directory '{}' as '{}' to avoid a name clash",
+                name, restored_name
+            );
+        }
+        tx.execute(
+            "UPDATE directories SET name = ?1, parent_id = ?2 WHERE id = ?3",
+            rusqlite::params![restored_name, parent_id, id],
+        )?;
+        tx.execute(
+            "WITH RECURSIVE subtree(id) AS (
+                SELECT ?1
+                UNION ALL
+                SELECT d.id FROM directories d JOIN subtree s ON d.parent_id = s.id
+                WHERE d.deleted_at = ?2
+             )
+             UPDATE directories SET deleted_at = NULL WHERE id IN subtree",
+            rusqlite::params![id, deleted_at],
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+}
+pub fn empty_trash() -> AppResult<()> {
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM paste_sheets WHERE deleted_at IS NOT NULL", [])?;
+        tx.execute("DELETE FROM directories WHERE deleted_at IS NOT NULL", [])?;
+        tx.commit()?;
+        Ok(())
+    })
+}
+pub fn purge_expired(retention_days: i64) -> AppResult<usize> {
+    let cutoff = format!("-{} days", retention_days.max(0));
+    db::with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let items = tx.execute(
+            "DELETE FROM paste_sheets WHERE deleted_at < datetime('now', ?1)",
+            [&cutoff],
+        )?;
+        let directories = tx.execute(
+            "DELETE FROM directories WHERE deleted_at < datetime('now', ?1)",
+            [&cutoff],
+        )?;
+        tx.commit()?;
+        Ok(items + directories)
+    })
+}
+pub fn retention_days() -> i64 {
+    settings::get_integer(settings::TRASH_RETENTION_DAYS)
+}
+pub fn start_purge_scheduler() {
+    thread::spawn(|| loop {
+        match purge_expired(retention_days()) {
+            Ok(0) => {}
+            Ok(purged) => info!("Purged {} expired trash entries", purged),
+            Err(e) => error!("Failed to purge trash: {:?}", e),
+        }
+        thread::sleep(PURGE_INTERVAL);
+    });
+}
@@ -1,4 +1,5 @@
 #![allow(unexpected_cfgs)]
+use crate::modules::settings;
 use log::debug;
 use std::sync::Mutex;
 use tauri::{AppHandle, Emitter, LogicalPosition, Manager, Runtime};
@@ -124,6 +125,11 @@ pub fn update_mouse_edge_enabled(enabled: bool) {
     MOUSE_EDGE_ENABLED.store(enabled, std::sync::atomic::Ordering::Relaxed);
     debug!("🖱 Mouse edge detection enabled: {}", enabled);
 }
+pub fn subscribe_settings() {
+    settings::subscribe(settings::MOUSE_EDGE_ENABLED, |change| {
+        update_mouse_edge_enabled(change.new_value == "true")
+    });
+}
 #[cfg(target_os = "macos")]
 fn setup_mouse_event_monitoring<R: Runtime>(app: AppHandle<R>) {
     use std::thread;